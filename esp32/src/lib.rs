@@ -7,6 +7,7 @@ use esp_backtrace as _;
 
 mod flex;
 pub mod parallel;
+pub mod port;
 pub mod serial;
 
 pub trait In = esp_hal::peripheral::Peripheral<P: esp_hal::gpio::InputPin> + 'static;
@@ -1,6 +1,6 @@
-use super::{flex::Flex as MyFlex, In, Out};
+use super::{flex::Flex as MyFlex, port::Port, In, Out};
 use esp_hal::gpio::*;
-use st7920::parallel::interface::*;
+use st7920::parallel::{interface::*, Timing};
 
 pub fn new_4bit<'a, const NUM: usize>(
     rs: impl Out + 'a,
@@ -22,6 +22,7 @@ pub fn new_4bit<'a, const NUM: usize>(
             Flex::new(db7),
         ]
         .map(MyFlex),
+        Timing::V3_3,
     )
 }
 
@@ -53,5 +54,40 @@ pub fn new_8bit<'a, const NUM: usize>(
             Flex::new(db7),
         ]
         .map(MyFlex),
+        Timing::V3_3,
+    )
+}
+
+/// Like [`new_4bit()`] but writing and reading the data bus all at once,
+/// DB4-7 must be among GPIO0-31
+pub fn new_4bit_port<'a, const NUM: usize>(
+    rs: impl Out + 'a,
+    rw: impl Out + 'a,
+    e: [impl Out + 'a; NUM],
+    db: [AnyPin; 4],
+) -> Interface<Output<'a>, Port<'a, 4>, NUM, 4> {
+    Interface::new(
+        Output::new(rs, Level::Low),
+        Output::new(rw, Level::Low),
+        e.map(|e| Output::new(e, Level::Low)),
+        Port::new(db),
+        Timing::V3_3,
+    )
+}
+
+/// Like [`new_8bit()`] but writing and reading the data bus all at once,
+/// DB0-7 must be among GPIO0-31
+pub fn new_8bit_port<'a, const NUM: usize>(
+    rs: impl Out + 'a,
+    rw: impl Out + 'a,
+    e: [impl Out + 'a; NUM],
+    db: [AnyPin; 8],
+) -> Interface<Output<'a>, Port<'a, 8>, NUM, 8> {
+    Interface::new(
+        Output::new(rs, Level::Low),
+        Output::new(rw, Level::Low),
+        e.map(|e| Output::new(e, Level::Low)),
+        Port::new(db),
+        Timing::V3_3,
     )
 }
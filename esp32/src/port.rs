@@ -0,0 +1,91 @@
+use core::convert::Infallible;
+use esp_hal::gpio::{AnyPin, Flex, Pin, Pull};
+use esp_hal::peripherals::GPIO;
+use st7920::hal::{OutPort, ParallelPort};
+
+/// Pins among GPIO0-31, written with a single pair of W1TS/W1TC writes
+/// and read with a single IN read
+pub struct Port<'a, const N: usize> {
+    _pins: [Flex<'a>; N],
+    /// Mask of each pin in the GPIO registers
+    bits: [u32; N],
+}
+
+impl<'a, const N: usize> Port<'a, N> {
+    pub fn new(pins: [AnyPin; N]) -> Self {
+        assert!(
+            pins.iter().all(|pin| pin.number() < 32),
+            "data bus pins must be among GPIO0-31"
+        );
+        let bits = pins.each_ref().map(|pin| 1 << pin.number());
+        // Route both the output and the input of each pin to the GPIO
+        // matrix, after that switching direction only takes the enable bits
+        let pins = pins.map(|pin| {
+            let mut pin = Flex::new(pin);
+            pin.set_as_output();
+            pin.set_as_input(Pull::None);
+            pin
+        });
+        Self { _pins: pins, bits }
+    }
+
+    fn mask(&self) -> u32 {
+        self.bits.iter().fold(0, |mask, bit| mask | bit)
+    }
+
+    fn gpio() -> &'static <GPIO as core::ops::Deref>::Target {
+        // SAFETY: PTR points to the GPIO register block, which is valid for
+        // the whole program. Only the W1TS/W1TC and IN registers are
+        // accessed through it: they set, clear or read the given bits
+        // atomically, so they can't race with the HAL on the other pins.
+        unsafe { &*GPIO::PTR }
+    }
+}
+
+impl<const N: usize> OutPort for Port<'_, N> {
+    type Error = Infallible;
+
+    const WIDTH: usize = N;
+
+    fn set_as_output(&mut self) -> Result<(), Self::Error> {
+        let mask = self.mask();
+        // SAFETY: any value is valid for ENABLE_W1TS, and the mask only
+        // has the bits of the pins owned by the port
+        Self::gpio()
+            .enable_w1ts()
+            .write(|w| unsafe { w.bits(mask) });
+        Ok(())
+    }
+
+    fn write(&mut self, data: u8) -> Result<(), Self::Error> {
+        let set = (self.bits.iter().enumerate())
+            .filter(|&(i, _)| data & (1 << i) != 0)
+            .fold(0, |set, (_, bit)| set | bit);
+        let gpio = Self::gpio();
+        // SAFETY: any value is valid for OUT_W1TS and OUT_W1TC, and both
+        // values only have the bits of the pins owned by the port
+        gpio.out_w1ts().write(|w| unsafe { w.bits(set) });
+        gpio.out_w1tc()
+            .write(|w| unsafe { w.bits(!set & self.mask()) });
+        Ok(())
+    }
+}
+
+impl<const N: usize> ParallelPort for Port<'_, N> {
+    fn set_as_input(&mut self) -> Result<(), Self::Error> {
+        let mask = self.mask();
+        // SAFETY: any value is valid for ENABLE_W1TC, and the mask only
+        // has the bits of the pins owned by the port
+        Self::gpio()
+            .enable_w1tc()
+            .write(|w| unsafe { w.bits(mask) });
+        Ok(())
+    }
+
+    fn read(&mut self) -> Result<u8, Self::Error> {
+        let levels = Self::gpio().in_().read().bits();
+        Ok((self.bits.iter().enumerate())
+            .filter(|&(_, bit)| levels & bit != 0)
+            .fold(0, |data, (i, _)| data | 1 << i))
+    }
+}
@@ -1,5 +1,4 @@
 #![no_std]
-#![feature(array_try_from_fn)]
 #![feature(never_type)]
 
 pub mod cgram;
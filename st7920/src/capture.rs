@@ -0,0 +1,136 @@
+//! Read back what is on the screen of a 128x64 display
+//!
+//! [`capture_gdram()`] reads the graphics into a [`Framebuffer`], which can
+//! be exported as a [PBM](Framebuffer::pbm) image, while [`capture_ddram()`]
+//! reads the text into a [`Text`].
+//! The text is drawn with the font of the controller, which can't be read,
+//! so it's kept separate from the graphics.
+//!
+//! Only the part of the RAMs shown without vertical scrolling is read.
+//! As the first read after setting the address returns stale data,
+//! a dummy read is done each time the address is set.
+
+use core::fmt;
+
+use crate::framebuffer::{self, Framebuffer, HEIGHT, WORDS};
+use crate::{ext, ExecuteRead};
+
+/// The characters on the screen
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Text {
+    /// Words of each line, either two half-width characters or a full-width one
+    pub lines: [[u16; WORDS]; 4],
+}
+
+impl Text {
+    /// Every line filled with spaces, as after a [`Clear`](crate::Command::Clear)
+    pub const BLANK: Self = Self {
+        lines: [[0x2020; WORDS]; 4],
+    };
+
+    /// DDRAM address of the first word of each line
+    pub const ADDRESSES: [u8; 4] = [0x00, 0x10, 0x08, 0x18];
+
+    /// DDRAM address of the first word of the given line,
+    /// `None` past the last one
+    pub fn address(line: usize) -> Option<u8> {
+        Self::ADDRESSES.get(line).copied()
+    }
+}
+
+/// Half-width characters are shown when printable,
+/// any other byte (including those of full-width characters) as `.`
+impl fmt::Display for Text {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for line in &self.lines {
+            for byte in line.iter().flat_map(|word| word.to_be_bytes()) {
+                match byte {
+                    0x20..0x7F => write!(f, "{}", byte as char)?,
+                    _ => f.write_str(".")?,
+                }
+            }
+            writeln!(f)?;
+        }
+        Ok(())
+    }
+}
+
+/// Read the graphics on the screen
+///
+/// Selecting the _Extended instruction set_ also turns the graphic display
+/// on or off, so `graphic` tells whether it is on, to leave it as it is.
+/// The _Basic instruction set_ is left selected.
+pub fn capture_gdram<Lcd, E>(mut lcd: Lcd, graphic: bool) -> Result<Framebuffer, E>
+where
+    Lcd: ext::Execute<Error = E> + ExecuteRead<Error = E>,
+{
+    let mut fb = Framebuffer::new();
+    match graphic {
+        true => lcd.select_graphic()?,
+        false => lcd.select_extended()?,
+    }
+    for row in 0..HEIGHT as u8 / 2 {
+        let (x, y) = framebuffer::address(0, row);
+        lcd.graphic_ram_addr(x, y)?;
+        lcd.read()?;
+        // The Address Counter moves on to the bottom half of the screen
+        for half in [row, row + HEIGHT as u8 / 2] {
+            for word in fb.row_mut(half as usize) {
+                *word = lcd.read()?;
+            }
+        }
+    }
+    lcd.select_basic()?;
+    Ok(fb)
+}
+
+/// Read the characters on the screen
+///
+/// The _Basic instruction set_ is left selected.
+pub fn capture_ddram<Lcd, E>(mut lcd: Lcd) -> Result<Text, E>
+where
+    Lcd: ext::Execute<Error = E> + ExecuteRead<Error = E>,
+{
+    let mut words = [0; 4 * WORDS];
+    lcd.select_basic()?;
+    lcd.ddram_addr(0)?;
+    lcd.read()?;
+    for word in &mut words {
+        *word = lcd.read()?;
+    }
+
+    let line = |addr: u8| core::array::from_fn(|i| words[addr as usize + i]);
+    Ok(Text {
+        lines: Text::ADDRESSES.map(line),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::mock::Emulator;
+
+    use super::*;
+
+    #[test]
+    fn gdram_capture_keeps_the_graphic_display() {
+        for graphic in [false, true] {
+            let mut lcd = Emulator::new();
+            for (y, row) in lcd.gdram.iter_mut().enumerate() {
+                for (x, word) in row.iter_mut().enumerate() {
+                    *word = (y << 8 | x) as u16;
+                }
+            }
+            lcd.graphic = graphic;
+
+            let fb = capture_gdram(&mut lcd, graphic).unwrap();
+            for row in 0..HEIGHT {
+                for (word, &data) in fb.row(row).iter().enumerate() {
+                    let (x, y) = framebuffer::address(word as u8, row as u8);
+                    assert_eq!(data, lcd.gdram[y as usize][x as usize]);
+                }
+            }
+            assert_eq!(lcd.graphic, graphic);
+            assert!(!lcd.extended);
+        }
+    }
+}
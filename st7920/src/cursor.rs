@@ -0,0 +1,179 @@
+//! Cursor placement and tracking
+//!
+//! The cursor is shown at the Address Counter, on a whole word (a full-width
+//! character or two half-width ones), so writing anywhere in the DDRAM moves
+//! it. [`Cursor`] keeps track of where the cursor should be, moving it along
+//! the text written at it and putting it back after writes elsewhere.
+//!
+//! The _Basic instruction set_ must be selected.
+
+use crate::capture::Text;
+use crate::framebuffer::WORDS;
+use crate::Execute;
+
+/// How the cursor is shown
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Style {
+    /// A line under the word
+    Underline,
+    /// The word blinks
+    Blink,
+    Both,
+}
+
+/// DDRAM address of the given line and column (in words),
+/// `None` when the position is not on the screen
+fn address(line: u8, column: u8) -> Option<u8> {
+    let start = Text::address(line as usize)?;
+    (column < WORDS as u8).then_some(start + column)
+}
+
+/// Line and column (in words) of the given DDRAM address, if it's on the screen
+fn position(addr: u8) -> Option<(u8, u8)> {
+    let start = addr & !(WORDS as u8 - 1);
+    let line = Text::ADDRESSES.iter().position(|&line| line == start)?;
+    Some((line as u8, addr % WORDS as u8))
+}
+
+pub struct Cursor<T> {
+    lcd: T,
+    /// DDRAM address of the cursor
+    addr: u8,
+    style: Style,
+    visible: bool,
+}
+
+impl<T> Cursor<T> {
+    /// Wrap a display with the cursor hidden at the home position
+    pub fn new(lcd: T, style: Style) -> Self {
+        Self {
+            lcd,
+            addr: 0,
+            style,
+            visible: false,
+        }
+    }
+
+    /// Line and column (in words) of the cursor,
+    /// `None` when writing moved it past the last line
+    pub fn position(&self) -> Option<(u8, u8)> {
+        position(self.addr)
+    }
+
+    pub fn release(self) -> T {
+        self.lcd
+    }
+}
+
+impl<T: Execute> Cursor<T> {
+    fn display(&mut self) -> Result<(), T::Error> {
+        let (underline, blink) = match self.style {
+            Style::Underline => (true, false),
+            Style::Blink => (false, true),
+            Style::Both => (true, true),
+        };
+        let visible = self.visible;
+        (self.lcd).display_on_off(true, visible && underline, visible && blink)
+    }
+
+    pub fn show(&mut self) -> Result<(), T::Error> {
+        self.visible = true;
+        self.display()
+    }
+
+    pub fn hide(&mut self) -> Result<(), T::Error> {
+        self.visible = false;
+        self.display()
+    }
+
+    pub fn set_style(&mut self, style: Style) -> Result<(), T::Error> {
+        self.style = style;
+        self.display()
+    }
+
+    /// Move the cursor to the given line and column (in words)
+    ///
+    /// A position past the end of the screen is clamped to the last line
+    /// and the last word of a line.
+    pub fn move_to(&mut self, line: u8, column: u8) -> Result<(), T::Error> {
+        let line = (line as usize).min(Text::ADDRESSES.len() - 1);
+        self.addr = Text::ADDRESSES[line] + column.min(WORDS as u8 - 1);
+        self.lcd.ddram_addr(self.addr)
+    }
+
+    /// Write at the cursor, moving it past the words written
+    ///
+    /// As with the Address Counter, the end of a line is followed by
+    /// the start of the line in the other half of the screen.
+    pub fn write(&mut self, words: &[u16]) -> Result<(), T::Error> {
+        self.lcd.write_burst(words)?;
+        self.addr = self.addr.wrapping_add(words.len() as u8) & 0b1111111;
+        Ok(())
+    }
+
+    /// Write somewhere else in the DDRAM, leaving the cursor where it is
+    ///
+    /// Nothing is written at a position past the end of the screen.
+    pub fn write_at(&mut self, line: u8, column: u8, words: &[u16]) -> Result<(), T::Error> {
+        let Some(addr) = address(line, column) else {
+            return Ok(());
+        };
+        self.update(|lcd| {
+            lcd.ddram_addr(addr)?;
+            lcd.write_burst(words)
+        })
+    }
+
+    /// Run the closure, that may move the Address Counter,
+    /// and put the cursor back where it was
+    pub fn update<R>(
+        &mut self,
+        run: impl FnOnce(&mut T) -> Result<R, T::Error>,
+    ) -> Result<R, T::Error> {
+        let result = run(&mut self.lcd)?;
+        self.lcd.ddram_addr(self.addr)?;
+        Ok(result)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::mock::emulator::{Address, Emulator};
+
+    use super::*;
+
+    #[test]
+    fn positions_round_trip() {
+        let mut cursor = Cursor::new(Emulator::new(), Style::Underline);
+        for line in 0..4 {
+            for column in 0..WORDS as u8 {
+                cursor.move_to(line, column).unwrap();
+                assert_eq!(cursor.position(), Some((line, column)));
+            }
+        }
+        cursor.move_to(2, 3).unwrap();
+        cursor.write_at(1, 0, &[0x4142]).unwrap();
+        assert_eq!(cursor.release().address, Address::DdRam(0x08 + 3));
+    }
+
+    #[test]
+    fn moves_past_the_end_are_clamped() {
+        let mut cursor = Cursor::new(Emulator::new(), Style::Underline);
+        cursor.move_to(4, 0).unwrap();
+        assert_eq!(cursor.position(), Some((3, 0)));
+        cursor.move_to(1, WORDS as u8).unwrap();
+        assert_eq!(cursor.position(), Some((1, WORDS as u8 - 1)));
+        cursor.move_to(u8::MAX, u8::MAX).unwrap();
+        assert_eq!(cursor.position(), Some((3, WORDS as u8 - 1)));
+        assert_eq!(cursor.release().address, Address::DdRam(0x18 + 7));
+    }
+
+    #[test]
+    fn writes_past_the_end_are_dropped() {
+        let mut cursor = Cursor::new(Emulator::new(), Style::Underline);
+        cursor.write_at(0, WORDS as u8, &[0x4142]).unwrap();
+        cursor.write_at(4, 0, &[0x4142]).unwrap();
+        let lcd = cursor.release();
+        assert!(lcd.ddram.iter().all(|&word| word == 0x2020));
+    }
+}
@@ -1,4 +1,4 @@
-use crate::hal;
+use crate::{hal, Oscillator};
 
 #[derive(Clone, Copy, Debug, PartialEq, Eq)]
 pub enum Command {
@@ -33,6 +33,16 @@ pub enum Command {
     ///
     /// > Make sure to run the [`SelectScroll`](Command::ScrollOffset) command first.
     ScrollOffset(u8),
+    /// Set the Icon RAM (IRAM) address
+    ///
+    /// Instruction Set: **Extended**
+    ///
+    /// > This command shares its encoding with [`ScrollOffset`](Command::ScrollOffset),
+    /// > make sure to run the [`EnableCgRam`](Command::EnableCgRam) command first.
+    ///
+    /// After sending this command every write operation
+    /// happens on the IRAM
+    IconRamAddr(u8),
     /// Set the Graphic RAM address
     ///
     /// Instruction Set: **Graphic**
@@ -43,10 +53,16 @@ pub enum Command {
 }
 
 impl Command {
-    /// Execution time of the [`Command`] in microseconds
+    /// Execution time of the [`Command`] with the [nominal](Oscillator::NOMINAL)
+    /// oscillator frequency
     pub fn execution_time(self) -> hal::Duration {
         use fugit::ExtU64;
-        1_600.micros()
+        72.micros()
+    }
+
+    /// Execution time of the [`Command`] with the given oscillator
+    pub fn execution_time_at(self, oscillator: &Oscillator) -> hal::Duration {
+        oscillator.scale(self.execution_time())
     }
 
     pub fn into_bytes(self) -> [u8; 2] {
@@ -58,13 +74,51 @@ impl Command {
             Reverse(line) => 0b100 | (line & 0b11),
             SelectExtended => 0b100100,
             SelectGraphic => 0b100110,
-            ScrollOffset(offset) => 0b1000000 | (offset & 0b11111),
-            GraphicRamAddr { y, x } => return [y & 0b1111, x & 0b111111].map(|b| 0b10000000 | b),
+            ScrollOffset(offset) => 0b1000000 | (offset & 0b111111),
+            IconRamAddr(addr) => 0b1000000 | (addr & 0b1111),
+            GraphicRamAddr { y, x } => return [y & 0b111111, x & 0b1111].map(|b| 0b10000000 | b),
         };
         [byte, 0]
     }
 }
 
+impl TryFrom<[u8; 2]> for Command {
+    type Error = u8;
+
+    /// Decode an instruction of the _Extended instruction set_
+    ///
+    /// This is the inverse of [`Command::into_bytes()`]: the second byte
+    /// is only used by [`GraphicRamAddr`](Command::GraphicRamAddr),
+    /// which is the only instruction that starts with the highest bit set.
+    ///
+    /// The first byte is given back when it's not an _Extended_ instruction
+    /// (e.g. a function set without the extended bit set, which should be
+    /// decoded as a [`crate::Command`]).
+    ///
+    /// [`IconRamAddr`](Command::IconRamAddr) is decoded as a
+    /// [`ScrollOffset`](Command::ScrollOffset), as they only differ
+    /// by the command sent before them.
+    fn try_from([byte, second]: [u8; 2]) -> Result<Self, Self::Error> {
+        use Command::*;
+        let is = |b: u8| byte & 1 << b != 0;
+        Ok(match byte.leading_zeros() {
+            0 => GraphicRamAddr {
+                y: byte & 0b111111,
+                x: second & 0b1111,
+            },
+            1 => ScrollOffset(byte & 0b111111),
+            2 if !is(2) => return Err(byte),
+            2 if is(1) => SelectGraphic,
+            2 => SelectExtended,
+            5 => Reverse(byte & 0b11),
+            6 if is(0) => EnableScroll,
+            6 => EnableCgRam,
+            7 => StandBy,
+            _ => return Err(byte),
+        })
+    }
+}
+
 pub trait Execute: super::Execute {
     fn execute_ext(&mut self, command: Command) -> Result<(), Self::Error>;
 
@@ -96,7 +150,17 @@ pub trait Execute: super::Execute {
         Execute::execute_ext(self, Command::ScrollOffset(offset))
     }
 
+    fn icon_ram_addr(&mut self, addr: u8) -> Result<(), Self::Error> {
+        Execute::execute_ext(self, Command::IconRamAddr(addr))
+    }
+
     fn graphic_ram_addr(&mut self, x: u8, y: u8) -> Result<(), Self::Error> {
         Execute::execute_ext(self, Command::GraphicRamAddr { y, x })
     }
 }
+
+impl<T: Execute> Execute for &mut T {
+    fn execute_ext(&mut self, command: Command) -> Result<(), Self::Error> {
+        T::execute_ext(self, command)
+    }
+}
@@ -0,0 +1,122 @@
+//! Copy of the pixels on the screen of a 128x64 display
+//!
+//! The GDRAM is 16 words wide and 32 rows high for such a display: the
+//! first 8 words of each row hold the top half of the screen, and the last
+//! 8 words the bottom half, so row `y + 32` of the screen is at `y` in the
+//! GDRAM, 8 words to the right.
+
+use core::fmt;
+
+use either::Either::{Left, Right};
+
+/// Width of the screen, in pixels
+pub const WIDTH: usize = 128;
+/// Height of the screen, in pixels
+pub const HEIGHT: usize = 64;
+/// Words in a row of the screen
+pub const WORDS: usize = WIDTH / 16;
+
+/// GDRAM address `(x, y)` of the given word of a row of the screen
+pub fn address(word: u8, row: u8) -> (u8, u8) {
+    match row < HEIGHT as u8 / 2 {
+        true => (word, row),
+        false => (word + WORDS as u8, row - HEIGHT as u8 / 2),
+    }
+}
+
+/// Pixels of the screen, the most significant bit of each
+/// word being the leftmost pixel as in the GDRAM
+#[derive(Clone, PartialEq, Eq)]
+pub struct Framebuffer {
+    rows: [[u16; WORDS]; HEIGHT],
+}
+
+impl Framebuffer {
+    /// A blank screen
+    pub const fn new() -> Self {
+        Self {
+            rows: [[0; WORDS]; HEIGHT],
+        }
+    }
+
+    pub fn row(&self, y: usize) -> &[u16; WORDS] {
+        &self.rows[y]
+    }
+
+    pub fn row_mut(&mut self, y: usize) -> &mut [u16; WORDS] {
+        &mut self.rows[y]
+    }
+
+    pub fn pixel(&self, x: usize, y: usize) -> bool {
+        self.rows[y][x / 16] & 0x8000 >> (x % 16) != 0
+    }
+
+    pub fn set_pixel(&mut self, x: usize, y: usize, on: bool) {
+        let (word, bit) = (&mut self.rows[y][x / 16], 0x8000 >> (x % 16));
+        match on {
+            true => *word |= bit,
+            false => *word &= !bit,
+        }
+    }
+
+    /// The screen as a plain PBM image
+    pub fn pbm(&self) -> Pbm<'_> {
+        Pbm(self)
+    }
+}
+
+impl Default for Framebuffer {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+// - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - -
+
+/// Plain PBM (`P1`) image of a [`Framebuffer`], pixels on being black
+pub struct Pbm<'a>(&'a Framebuffer);
+
+/// Half a row of pixels, keeping the lines within
+/// the 70 characters allowed by the format
+struct Half<'a> {
+    fb: &'a Framebuffer,
+    y: usize,
+    x: usize,
+}
+
+impl fmt::Display for Half<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        (self.x..self.x + WIDTH / 2).try_for_each(|x| match self.fb.pixel(x, self.y) {
+            true => f.write_str("1"),
+            false => f.write_str("0"),
+        })
+    }
+}
+
+impl Pbm<'_> {
+    /// Lines of the image, without the line endings
+    pub fn lines(&self) -> impl Iterator<Item = impl fmt::Display + '_> {
+        let header = ["P1", "128 64"].map(Left);
+        let pixels = (0..HEIGHT * 2).map(|i| {
+            Right(Half {
+                fb: self.0,
+                y: i / 2,
+                x: i % 2 * WIDTH / 2,
+            })
+        });
+        header.into_iter().chain(pixels)
+    }
+
+    /// Log the image a line at the time, to keep each record short
+    pub fn log(&self, level: log::Level) {
+        for line in self.lines() {
+            log::log!(level, "{line}");
+        }
+    }
+}
+
+impl fmt::Display for Pbm<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        self.lines().try_for_each(|line| writeln!(f, "{line}"))
+    }
+}
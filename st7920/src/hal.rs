@@ -5,14 +5,22 @@ pub use embedded_hal::digital::{ErrorType, InputPin, OutputPin};
 pub type Instant = fugit::Instant<u64, 1, 1_000_000>;
 pub type Duration = fugit::Duration<u64, 1, 1_000_000>;
 
+#[cfg(any(test, feature = "std"))]
+pub mod clock;
+#[cfg(any(test, feature = "std"))]
+pub use clock::{now, sleep_until};
+
+#[cfg(not(any(test, feature = "std")))]
 extern "Rust" {
     fn _st7920_now() -> Instant;
 }
 
+#[cfg(not(any(test, feature = "std")))]
 pub fn now() -> Instant {
     unsafe { _st7920_now() }
 }
 
+#[cfg(not(any(test, feature = "std")))]
 pub fn sleep_until(end: Instant) {
     while now() < end {}
 }
@@ -21,6 +29,18 @@ pub fn sleep(duration: impl Into<Duration>) {
     sleep_until(now() + duration.into());
 }
 
+/// The amount of [`now()`] ticks to sleep for to wait at least the given time
+///
+/// Since [`now()`] has a resolution of one microsecond, non-zero times
+/// are rounded up to the next microsecond, plus one since the current one may
+/// be about to end.
+pub fn at_least(time: fugit::NanosDurationU32) -> Duration {
+    match time.ticks() {
+        0 => Duration::from_ticks(0),
+        ns => Duration::from_ticks(ns.div_ceil(1000) as u64 + 1),
+    }
+}
+
 /// Generic output pin
 pub trait OutPin: ErrorType + OutputPin {
     fn set_as_output(&mut self) -> Result<(), Self::Error>;
@@ -33,3 +53,77 @@ pub trait InPin: ErrorType + InputPin {
 
 /// Generic input/output pin
 pub trait IoPin = InPin + OutPin;
+
+/// Generic group of output pins, written all at once
+///
+/// Bit `i` of the data drives the `i`-th pin of the port,
+/// the bits beyond the width of the port are ignored.
+pub trait OutPort {
+    type Error;
+
+    /// Number of pins of the port
+    const WIDTH: usize;
+
+    fn set_as_output(&mut self) -> Result<(), Self::Error>;
+    fn write(&mut self, data: u8) -> Result<(), Self::Error>;
+}
+
+/// Generic group of input/output pins, written and read all at once
+pub trait ParallelPort: OutPort {
+    fn set_as_input(&mut self) -> Result<(), Self::Error>;
+    fn read(&mut self) -> Result<u8, Self::Error>;
+}
+
+/// A port made of separate pins, that are accessed one at the time
+impl<P: OutPin, const N: usize> OutPort for [P; N] {
+    type Error = P::Error;
+
+    const WIDTH: usize = N;
+
+    fn set_as_output(&mut self) -> Result<(), Self::Error> {
+        self.iter_mut().try_for_each(OutPin::set_as_output)
+    }
+
+    fn write(&mut self, data: u8) -> Result<(), Self::Error> {
+        for (i, pin) in self.iter_mut().enumerate() {
+            pin.set_state((data & (1 << i) != 0).into())?
+        }
+        Ok(())
+    }
+}
+
+impl<P: IoPin, const N: usize> ParallelPort for [P; N] {
+    fn set_as_input(&mut self) -> Result<(), Self::Error> {
+        self.iter_mut().try_for_each(InPin::set_as_input)
+    }
+
+    fn read(&mut self) -> Result<u8, Self::Error> {
+        self.iter_mut()
+            .rev()
+            .try_fold(0, |out, pin| Ok(out << 1 | pin.is_high()? as u8))
+    }
+}
+
+impl<T: OutPort> OutPort for &mut T {
+    type Error = T::Error;
+
+    const WIDTH: usize = T::WIDTH;
+
+    fn set_as_output(&mut self) -> Result<(), Self::Error> {
+        T::set_as_output(self)
+    }
+
+    fn write(&mut self, data: u8) -> Result<(), Self::Error> {
+        T::write(self, data)
+    }
+}
+
+impl<T: ParallelPort> ParallelPort for &mut T {
+    fn set_as_input(&mut self) -> Result<(), Self::Error> {
+        T::set_as_input(self)
+    }
+
+    fn read(&mut self) -> Result<u8, Self::Error> {
+        T::read(self)
+    }
+}
@@ -0,0 +1,65 @@
+//! Virtual clock for running on the host
+//!
+//! With the `std` feature, and in the tests of this crate, [`now()`](super::now)
+//! reads this clock instead of calling `_st7920_now`, and
+//! [`sleep_until()`](super::sleep_until) moves it forward instantly instead
+//! of spinning, so code using the driver runs without waiting, while still
+//! keeping track of how long it would have taken.
+//!
+//! Each thread has its own clock, so tests running in parallel don't interfere.
+
+use core::cell::Cell;
+
+use super::{Duration, Instant};
+
+const ZERO: Instant = Instant::from_ticks(0);
+
+std::thread_local! {
+    static NOW: Cell<Instant> = const { Cell::new(ZERO) };
+    static SLEPT: Cell<Duration> = const { Cell::new(Duration::from_ticks(0)) };
+}
+
+pub fn now() -> Instant {
+    NOW.get()
+}
+
+/// Move the clock forward by the given amount
+///
+/// This is how time spent doing something other than waiting
+/// (like shifting bits out on a bus) is simulated.
+pub fn advance(duration: Duration) {
+    NOW.set(NOW.get() + duration);
+}
+
+/// Move the clock forward up to the given instant,
+/// nothing happens if it's already past it
+pub fn sleep_until(end: Instant) {
+    let now = NOW.get();
+    if end > now {
+        SLEPT.set(SLEPT.get() + (end - now));
+        NOW.set(end);
+    }
+}
+
+/// Move the clock back to zero and forget the time slept
+pub fn reset() {
+    NOW.set(ZERO);
+    SLEPT.set(Duration::from_ticks(0));
+}
+
+/// Total simulated time since the last [`reset()`]
+pub fn elapsed() -> Duration {
+    NOW.get() - ZERO
+}
+
+/// Part of the [`elapsed()`] time that was spent sleeping
+pub fn slept() -> Duration {
+    SLEPT.get()
+}
+
+/// Run the closure and return how much simulated time it took
+pub fn measure<T>(run: impl FnOnce() -> T) -> (T, Duration) {
+    let start = NOW.get();
+    let result = run();
+    (result, NOW.get() - start)
+}
@@ -0,0 +1,200 @@
+//! Highlighting parts of the text
+//!
+//! [`Highlight`] remembers what is highlighted, so that highlighting it again
+//! does nothing and [`clear()`](Highlight::clear) undoes it.
+//! Whole lines are reversed with [`Reverse`](ext::Command::Reverse), which
+//! toggles and works on one line at the time, while other areas are inverted
+//! in the GDRAM, which shows through the text when the graphic display is on.
+//!
+//! [`Highlight`] doesn't know whether the graphic display is on: with a
+//! [`Screen`](crate::screen::Screen) in [`Mode::TextOnly`] the inverted
+//! areas aren't shown, and in [`Mode::GraphicsOnly`] they are shown without
+//! the text, so only whole lines should be highlighted in those modes.
+//!
+//! The _Extended instruction set_ must be selected.
+//!
+//! [`Mode::TextOnly`]: crate::screen::Mode::TextOnly
+//! [`Mode::GraphicsOnly`]: crate::screen::Mode::GraphicsOnly
+
+use core::ops::Range;
+
+use crate::pixels::{Fill, Pixels};
+use crate::{ext, ExecuteRead};
+
+/// Half-width characters in a line
+const COLUMNS: u8 = 16;
+/// Lines of text on the screen
+const LINES: u8 = 4;
+
+/// Part of the text, in lines and columns of half-width characters
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Area {
+    pub lines: Range<u8>,
+    pub columns: Range<u8>,
+}
+
+impl Area {
+    /// The whole given line
+    pub fn line(line: u8) -> Self {
+        Self {
+            lines: line..line.saturating_add(1),
+            columns: 0..COLUMNS,
+        }
+    }
+
+    fn whole_line(&self) -> Option<u8> {
+        (self.lines.len() == 1 && self.columns == (0..COLUMNS)).then_some(self.lines.start)
+    }
+}
+
+/// Inverts an area of the GDRAM, which needs to read it
+type Invert<T> = fn(&mut Pixels<T>, &Area) -> Result<(), <T as crate::Execute>::Error>;
+
+enum Current<T: ext::Execute> {
+    /// Reversed by the controller
+    Line(u8),
+    /// Inverted in the GDRAM, by the given function
+    Area(Area, Invert<T>),
+}
+
+pub struct Highlight<T: ext::Execute> {
+    pixels: Pixels<T>,
+    current: Option<Current<T>>,
+}
+
+impl<T: ext::Execute> Highlight<T> {
+    /// Wrap a display with nothing highlighted
+    pub fn new(lcd: T) -> Self {
+        Self {
+            pixels: Pixels::new(lcd),
+            current: None,
+        }
+    }
+
+    /// What is currently highlighted
+    pub fn current(&self) -> Option<Area> {
+        self.current.as_ref().map(|current| match current {
+            Current::Line(line) => Area::line(*line),
+            Current::Area(area, _) => area.clone(),
+        })
+    }
+
+    pub fn release(self) -> T {
+        self.pixels.release()
+    }
+
+    /// Highlight the given line, replacing what was highlighted before
+    ///
+    /// A line past the last one highlights nothing.
+    pub fn highlight(&mut self, line: u8) -> Result<(), T::Error> {
+        if line >= LINES {
+            return self.clear();
+        }
+        if let Some(Current::Line(current)) = self.current {
+            if current == line {
+                return Ok(());
+            }
+        }
+        self.clear()?;
+        self.pixels.lcd().reverse(line)?;
+        self.current = Some(Current::Line(line));
+        Ok(())
+    }
+
+    pub fn clear(&mut self) -> Result<(), T::Error> {
+        match &self.current {
+            // Reversing any line again returns the reversed one to normal
+            Some(Current::Line(line)) => self.pixels.lcd().reverse(*line)?,
+            Some(Current::Area(area, invert)) => invert(&mut self.pixels, area)?,
+            None => (),
+        }
+        self.current = None;
+        Ok(())
+    }
+}
+
+impl<T, E> Highlight<T>
+where
+    T: ext::Execute<Error = E> + ExecuteRead<Error = E>,
+{
+    /// Invert the pixels of the area in the GDRAM, characters being 8x16
+    fn invert(pixels: &mut Pixels<T>, area: &Area) -> Result<(), E> {
+        let (x, y) = (
+            area.columns.start as usize * 8,
+            area.lines.start as usize * 16,
+        );
+        let (width, height) = (area.columns.len() * 8, area.lines.len() * 16);
+        pixels.fill_rect(x, y, width, height, Fill::Invert)
+    }
+
+    /// Like [`highlight()`](Highlight::highlight) for any area,
+    /// which is inverted in the GDRAM unless it's a whole line
+    pub fn highlight_area(&mut self, area: Area) -> Result<(), E> {
+        if let Some(line) = area.whole_line() {
+            return self.highlight(line);
+        }
+        if self.current().as_ref() == Some(&area) {
+            return Ok(());
+        }
+        self.clear()?;
+        Self::invert(&mut self.pixels, &area)?;
+        self.current = Some(Current::Area(area, Self::invert));
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use hal::Duration;
+
+    use crate::ext::Execute;
+    use crate::mock::{Emulator, Recorder};
+    use crate::{hal, serial};
+
+    use super::*;
+
+    #[test]
+    fn lines_past_the_last_highlight_nothing() {
+        let mut lcd = Emulator::new();
+        lcd.select_extended().unwrap();
+        let mut highlight = Highlight::new(&mut lcd);
+        highlight.highlight(1).unwrap();
+        highlight.highlight(4).unwrap();
+        assert_eq!(highlight.current(), None);
+        highlight.highlight_area(Area::line(u8::MAX)).unwrap();
+        assert_eq!(lcd.reversed, None);
+    }
+
+    #[test]
+    fn highlighting_a_line_clears_the_area() {
+        let mut lcd = Emulator::new();
+        lcd.select_extended().unwrap();
+        let mut highlight = Highlight::new(&mut lcd);
+        highlight
+            .highlight_area(Area {
+                lines: 0..1,
+                columns: 0..8,
+            })
+            .unwrap();
+        highlight.highlight(2).unwrap();
+        assert_eq!(highlight.current(), Some(Area::line(2)));
+        assert_eq!(lcd.reversed, Some(2));
+        assert!(lcd.gdram.iter().flatten().all(|&word| word == 0));
+    }
+
+    #[test]
+    fn lines_are_highlighted_through_a_serial_interface() {
+        let recorder = Recorder::new();
+        let spi = recorder.spi("sclk", "sid", Duration::from_ticks(1));
+        let lcd = serial::Interface::new(spi, [recorder.pin("cs")]);
+        let mut highlight = Highlight::new(lcd);
+        highlight.highlight(1).unwrap();
+        highlight.highlight(1).unwrap();
+        highlight.highlight(2).unwrap();
+        highlight.clear().unwrap();
+
+        let reverse = |line: u8| [0xF8, 0x00, (0b100 | line) << 4];
+        let expected = [1, 1, 2, 2].map(reverse).concat();
+        assert_eq!(recorder.shifted("sclk", "sid"), expected);
+    }
+}
@@ -0,0 +1,166 @@
+//! Icon RAM (IRAM)
+//!
+//! The IRAM drives up to 240 icon segments, that some modules wire above
+//! the text area, as 15 words of 16 bits. Icon `i` is bit `15 - i % 16`
+//! of word `i / 16`, which segment it turns on depends on the module.
+//!
+//! [`Icons`] keeps a copy of the IRAM, which can't be read back, and only
+//! writes the words that change. The _Extended instruction set_ must be
+//! selected, and the [`EnableCgRam`](ext::Command::EnableCgRam) command
+//! is sent before each update, as it makes the IRAM address available.
+
+use crate::ext;
+
+/// Words in the IRAM
+pub const WORDS: usize = 15;
+/// Icons in the IRAM
+pub const ICONS: usize = WORDS * 16;
+
+/// State of every icon
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct IconSet {
+    pub words: [u16; WORDS],
+}
+
+impl IconSet {
+    /// Every icon off
+    pub const fn new() -> Self {
+        Self { words: [0; WORDS] }
+    }
+
+    /// # Panics
+    ///
+    /// When `icon` is not below [`ICONS`].
+    pub fn get(&self, icon: usize) -> bool {
+        self.words[icon / 16] & 0x8000 >> (icon % 16) != 0
+    }
+
+    /// # Panics
+    ///
+    /// When `icon` is not below [`ICONS`].
+    pub fn set(&mut self, icon: usize, on: bool) {
+        let (word, bit) = (&mut self.words[icon / 16], 0x8000 >> (icon % 16));
+        match on {
+            true => *word |= bit,
+            false => *word &= !bit,
+        }
+    }
+}
+
+impl Default for IconSet {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+pub struct Icons<T> {
+    lcd: T,
+    /// Contents of the IRAM, `None` before it's written the first time
+    current: Option<IconSet>,
+}
+
+impl<T> Icons<T> {
+    pub fn new(lcd: T) -> Self {
+        Self { lcd, current: None }
+    }
+
+    /// The icons shown, `None` until they are first [shown](Icons::show)
+    pub fn current(&self) -> Option<&IconSet> {
+        self.current.as_ref()
+    }
+
+    pub fn release(self) -> T {
+        self.lcd
+    }
+}
+
+impl<T: ext::Execute> Icons<T> {
+    /// Show the given icons, writing the words that changed
+    /// (every word the first time)
+    pub fn show(&mut self, icons: &IconSet) -> Result<(), T::Error> {
+        // The IRAM is unknown until every word was written
+        let current = self.current.take();
+        self.lcd.enable_cgram()?;
+        for (addr, &word) in icons.words.iter().enumerate() {
+            if current.is_some_and(|current| current.words[addr] == word) {
+                continue;
+            }
+            self.lcd.icon_ram_addr(addr as u8)?;
+            self.lcd.write(word)?;
+        }
+        self.current = Some(*icons);
+        Ok(())
+    }
+
+    /// Turn a single icon on or off, leaving the others as they are
+    /// (off, before icons are first shown)
+    ///
+    /// # Panics
+    ///
+    /// When `icon` is not below [`ICONS`].
+    pub fn set(&mut self, icon: usize, on: bool) -> Result<(), T::Error> {
+        let mut icons = self.current.unwrap_or_default();
+        icons.set(icon, on);
+        self.show(&icons)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use core::convert::Infallible;
+
+    use crate::mock::Emulator;
+    use crate::{Command, Execute};
+
+    use super::*;
+
+    /// Counts the words written to the emulator
+    struct Counting {
+        lcd: Emulator,
+        writes: usize,
+    }
+
+    impl Execute for Counting {
+        type Error = Infallible;
+
+        fn execute(&mut self, command: Command) -> Result<(), Self::Error> {
+            self.writes += matches!(command, Command::Write(_)) as usize;
+            self.lcd.execute(command)
+        }
+    }
+
+    impl ext::Execute for Counting {
+        fn execute_ext(&mut self, command: ext::Command) -> Result<(), Self::Error> {
+            self.lcd.execute_ext(command)
+        }
+    }
+
+    #[test]
+    fn only_changed_words_are_written() {
+        let mut lcd = Counting {
+            lcd: Emulator::new(),
+            writes: 0,
+        };
+        ext::Execute::select_extended(&mut lcd).unwrap();
+        let mut icons = Icons::new(&mut lcd);
+        icons.set(0, true).unwrap();
+        icons.set(ICONS - 1, true).unwrap();
+        icons.set(17, true).unwrap();
+        icons.set(0, false).unwrap();
+        let current = *icons.current().unwrap();
+
+        let mut expected = IconSet::new();
+        expected.set(ICONS - 1, true);
+        expected.set(17, true);
+        assert_eq!(current, expected);
+        assert_eq!(lcd.lcd.icons(), expected);
+        // Every word the first time, then one for each change
+        assert_eq!(lcd.writes, WORDS + 3);
+    }
+
+    #[test]
+    #[should_panic]
+    fn icons_past_the_last_panic() {
+        IconSet::new().set(ICONS, true);
+    }
+}
@@ -1,11 +1,26 @@
 #![no_std]
 #![feature(trait_alias)]
-#![feature(get_many_mut)]
 
+#[cfg(any(test, feature = "std"))]
+extern crate std;
+
+pub mod capture;
+pub mod cursor;
 pub mod ext;
+pub mod framebuffer;
 pub mod hal;
+pub mod highlight;
+pub mod icons;
+pub mod marquee;
+#[cfg(any(test, feature = "mock"))]
+pub mod mock;
 pub mod parallel;
+pub mod pixels;
+pub mod screen;
+pub mod scroller;
+pub mod selftest;
 pub mod serial;
+pub mod verify;
 
 fn bit<T: Into<u8>>(v: T, bit: u8) -> u8 {
     v.into() << bit
@@ -82,17 +97,76 @@ pub enum Command {
     DdRamAddr(u8),
 }
 
+/// The oscillator driving the controller
+///
+/// The execution times given by the datasheet are measured with the
+/// oscillator running at 540kHz, and they scale with its period.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Oscillator {
+    fosc: fugit::HertzU32,
+    margin: u8,
+}
+
+impl Oscillator {
+    /// The frequency the datasheet execution times are given at
+    pub const NOMINAL: Self = Self {
+        fosc: fugit::HertzU32::kHz(540),
+        margin: 0,
+    };
+
+    /// Create an oscillator running at the given frequency, adding
+    /// a safety margin (in percent) to the execution times
+    ///
+    /// Returns `None` when the frequency is zero.
+    pub const fn new(fosc: fugit::HertzU32, margin: u8) -> Option<Self> {
+        match fosc.raw() {
+            0 => None,
+            _ => Some(Self { fosc, margin }),
+        }
+    }
+
+    /// Frequency of the oscillator
+    pub const fn fosc(&self) -> fugit::HertzU32 {
+        self.fosc
+    }
+
+    /// Safety margin added to the execution times, in percent
+    pub const fn margin(&self) -> u8 {
+        self.margin
+    }
+
+    /// Scale an execution time given at the [nominal](Self::NOMINAL)
+    /// frequency to this oscillator, adding the safety margin
+    pub fn scale(&self, nominal: hal::Duration) -> hal::Duration {
+        let num = nominal.ticks() * Self::NOMINAL.fosc.raw() as u64 * (100 + self.margin as u64);
+        let den = self.fosc.raw() as u64 * 100;
+        hal::Duration::from_ticks(num.div_ceil(den))
+    }
+}
+
+impl Default for Oscillator {
+    fn default() -> Self {
+        Self::NOMINAL
+    }
+}
+
 impl Command {
-    /// Execution time of the [`Command`] in microseconds
+    /// Execution time of the [`Command`] with the [nominal](Oscillator::NOMINAL)
+    /// oscillator frequency
     pub fn execution_time(self) -> hal::Duration {
         use fugit::ExtU64;
         match self {
-            Self::Clear | Self::Write(_) => 72,
-            _ => 1_600,
+            Self::Clear | Self::Home => 1_600,
+            _ => 72,
         }
         .micros()
     }
 
+    /// Execution time of the [`Command`] with the given oscillator
+    pub fn execution_time_at(self, oscillator: &Oscillator) -> hal::Duration {
+        oscillator.scale(self.execution_time())
+    }
+
     pub fn into_byte(self) -> u8 {
         use Command::*;
         match self {
@@ -116,6 +190,42 @@ impl Command {
     }
 }
 
+impl TryFrom<u8> for Command {
+    type Error = u8;
+
+    /// Decode an instruction byte of the _Basic instruction set_
+    ///
+    /// This is the inverse of [`Command::into_byte()`], the byte is given back
+    /// when it's not a _Basic_ instruction (e.g. a function set with the
+    /// extended bit set, which should be decoded as an [`ext::Command`]).
+    fn try_from(byte: u8) -> Result<Self, Self::Error> {
+        use Command::*;
+        let is = |b: u8| byte & 1 << b != 0;
+        Ok(match byte.leading_zeros() {
+            0 => DdRamAddr(byte & 0b1111111),
+            1 => CgRamAddr(byte & 0b111111),
+            2 if is(2) => return Err(byte),
+            2 => SelectBasic,
+            3 => CursorDisplayCtrl {
+                sc: is(3),
+                rl: is(2),
+            },
+            4 => DisplayOnOff {
+                display: is(2),
+                cursor: is(1),
+                blink: is(0),
+            },
+            5 => EntryMode {
+                increment: is(1),
+                shift: is(0),
+            },
+            6 => Home,
+            7 => Clear,
+            _ => return Err(byte),
+        })
+    }
+}
+
 pub trait Execute {
     type Error;
 
@@ -139,6 +249,26 @@ pub trait Execute {
         self.execute(Command::Write(data))
     }
 
+    /// Write consecutive words into the currently selected RAM
+    ///
+    /// Interfaces that can stream data more efficiently than with
+    /// separate writes override both this and [`write_iter()`](Execute::write_iter).
+    fn write_burst(&mut self, data: &[u16]) -> Result<(), Self::Error> {
+        data.iter().try_for_each(|&word| self.write(word))
+    }
+
+    /// Like [`write_burst()`](Execute::write_burst) but
+    /// taking the words from an iterator
+    ///
+    /// Being generic, this method is not available on `dyn Execute`,
+    /// which can use [`write_burst()`](Execute::write_burst) instead.
+    fn write_iter(&mut self, data: impl IntoIterator<Item = u16>) -> Result<(), Self::Error>
+    where
+        Self: Sized,
+    {
+        data.into_iter().try_for_each(|word| self.write(word))
+    }
+
     fn clear(&mut self) -> Result<(), Self::Error> {
         self.execute(Command::Clear)
     }
@@ -195,6 +325,42 @@ pub trait ExecuteRead {
     }
 }
 
+impl<T: Execute> Execute for &mut T {
+    type Error = T::Error;
+
+    fn init(&mut self) -> Result<(), Self::Error> {
+        T::init(self)
+    }
+
+    fn execute(&mut self, command: Command) -> Result<(), Self::Error> {
+        T::execute(self, command)
+    }
+
+    fn write(&mut self, data: u16) -> Result<(), Self::Error> {
+        T::write(self, data)
+    }
+
+    fn write_burst(&mut self, data: &[u16]) -> Result<(), Self::Error> {
+        T::write_burst(self, data)
+    }
+
+    fn write_iter(&mut self, data: impl IntoIterator<Item = u16>) -> Result<(), Self::Error> {
+        T::write_iter(self, data)
+    }
+}
+
+impl<T: ExecuteRead> ExecuteRead for &mut T {
+    type Error = T::Error;
+
+    fn read(&mut self) -> Result<u16, Self::Error> {
+        T::read(self)
+    }
+
+    fn read_bf_ac(&mut self) -> Result<(bool, u8), Self::Error> {
+        T::read_bf_ac(self)
+    }
+}
+
 pub trait SharedBus {
     type Interface<'a>
     where
@@ -204,3 +370,91 @@ pub trait SharedBus {
 
     fn get(&mut self, index: usize) -> Option<Self::Interface<'_>>;
 }
+
+#[cfg(test)]
+mod tests {
+    use std::vec::Vec;
+
+    use crate::hal::{clock, Duration, Instant};
+    use crate::mock::{Pin, Recorder};
+    use crate::parallel::{Interface8Bit, Timing};
+
+    use super::*;
+
+    fn interface(recorder: &Recorder) -> Interface8Bit<Pin, Pin, 1> {
+        clock::reset();
+        let [rs, rw, e] = ["RS", "RW", "E"].map(|name| recorder.pin(name));
+        let bus = ["DB0", "DB1", "DB2", "DB3", "DB4", "DB5", "DB6", "DB7"];
+        Interface8Bit::new(rs, rw, [e], bus.map(|name| recorder.pin(name)), Timing::V5)
+    }
+
+    /// When each byte was latched
+    fn latches(recorder: &Recorder) -> Vec<Instant> {
+        let e = recorder.transitions("E").into_iter();
+        e.filter(|(_, level)| !level)
+            .map(|(time, _)| time)
+            .collect()
+    }
+
+    #[test]
+    fn oscillator_scales_execution_times() {
+        use fugit::HertzU32;
+        assert_eq!(Oscillator::new(HertzU32::Hz(0), 0), None);
+
+        let slow = Oscillator::new(HertzU32::kHz(270), 10).unwrap();
+        assert_eq!(slow.scale(Duration::micros(72)), Duration::micros(159));
+        let nominal = Command::Clear.execution_time_at(&Oscillator::NOMINAL);
+        assert_eq!(nominal, Command::Clear.execution_time());
+    }
+
+    #[test]
+    fn execute_is_object_safe() {
+        let recorder = Recorder::new();
+        let mut lcd = interface(&recorder);
+        let lcd: &mut dyn ext::Execute<Error = _> = &mut lcd;
+        lcd.write_burst(&[0x4142, 0x4344]).unwrap();
+        assert_eq!(latches(&recorder).len(), 4);
+    }
+
+    #[test]
+    fn init_respects_the_datasheet_waits() {
+        let recorder = Recorder::new();
+        let mut lcd = interface(&recorder);
+        let ((), time) = clock::measure(|| lcd.init().unwrap());
+
+        // Power on, function set twice, display on, clear and entry mode
+        let latches = latches(&recorder);
+        assert_eq!(latches.len(), 5);
+        assert!(latches[0] - Instant::from_ticks(0) >= Duration::millis(40));
+        let gaps: Vec<_> = latches.windows(2).map(|w| w[1] - w[0]).collect();
+        let required = [100, 37, 100, 10_000].map(Duration::micros);
+        for (gap, required) in gaps.into_iter().zip(required) {
+            assert!(gap >= required, "{gap} < {required}");
+        }
+
+        assert_eq!(time, clock::elapsed());
+        assert!(clock::slept() <= time);
+    }
+
+    #[test]
+    fn writes_wait_for_the_execution_time() {
+        let recorder = Recorder::new();
+        let mut lcd = interface(&recorder);
+        lcd.clear().unwrap();
+        let ((), time) = clock::measure(|| lcd.write_burst(&[0x4142; 8]).unwrap());
+
+        // The first write waits for the clear, the others for the previous write
+        let [clear, write] = [1_600, 72].map(Duration::micros);
+        assert!(time >= clear + write * 7);
+        let latches = latches(&recorder);
+        let words = latches[1..]
+            .chunks(2)
+            .map(|word| word[0])
+            .collect::<Vec<_>>();
+        assert_eq!(words.len(), 8);
+        assert!(words[0] - latches[0] >= clear);
+        for pair in words.windows(2) {
+            assert!(pair[1] - pair[0] >= write);
+        }
+    }
+}
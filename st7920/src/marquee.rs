@@ -0,0 +1,178 @@
+//! Horizontal marquee using the display shift
+//!
+//! [`CursorDisplayCtrl`](crate::Command::CursorDisplayCtrl) with `sc` set shifts
+//! the whole display by a word (two half-width characters), moving a window
+//! over rows of 16 words that wrap around. On a 128x64 display the top half
+//! of the screen shows the first 8 words of the window and the bottom half
+//! the next 8, so the text of a row starts on a line of the top half and
+//! goes on in the line 2 below.
+//!
+//! [`Marquee`] scrolls a text through each of the first two rows, writing the
+//! word that was just moved around to the other end with the next part of
+//! the text. The shift moves every line: the rows without a text keep going
+//! around as they are.
+//!
+//! The _Basic instruction set_ must be selected.
+
+use crate::hal::{now, sleep_until, Duration, Instant};
+use crate::Execute;
+
+/// Words in a row of the DDRAM
+const ROW_WORDS: u8 = 16;
+
+/// Which way the text moves
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Direction {
+    Left,
+    Right,
+}
+
+/// Word of the text at the given index, the text being padded with spaces
+/// to at least a row and repeated
+fn word(text: &[u8], index: isize) -> u16 {
+    let len = text.len().div_ceil(2).max(ROW_WORDS as usize) as isize;
+    let i = index.rem_euclid(len) as usize * 2;
+    let byte = |i: usize| text.get(i).copied().unwrap_or(b' ');
+    u16::from_be_bytes([byte(i), byte(i + 1)])
+}
+
+pub struct Marquee<T> {
+    lcd: T,
+    direction: Direction,
+    /// Time between shifts
+    period: Duration,
+    next: Instant,
+    /// Word of the rows at the start of the window
+    shift: u8,
+    /// Word of the texts at the start of the window
+    position: isize,
+}
+
+impl<T> Marquee<T> {
+    /// Shift the display in the given direction every `period`
+    pub fn new(lcd: T, direction: Direction, period: Duration) -> Self {
+        Self {
+            lcd,
+            direction,
+            period,
+            next: now(),
+            shift: 0,
+            position: 0,
+        }
+    }
+
+    pub fn set_direction(&mut self, direction: Direction) {
+        self.direction = direction;
+    }
+
+    pub fn set_period(&mut self, period: Duration) {
+        self.period = period;
+    }
+
+    pub fn release(self) -> T {
+        self.lcd
+    }
+}
+
+impl<T: Execute> Marquee<T> {
+    /// Go to the home position and write the beginning of each text into
+    /// its row: the first one through lines 0 and 2, the second one 1 and 3
+    pub fn start(&mut self, texts: &[&[u8]]) -> Result<(), T::Error> {
+        self.lcd.home()?;
+        (self.shift, self.position) = (0, 0);
+        for (row, text) in texts.iter().take(2).enumerate() {
+            let words = (0..ROW_WORDS as isize).map(|i| word(text, i));
+            self.lcd.ddram_addr(row as u8 * 0x10)?;
+            self.lcd.write_iter(words)?;
+        }
+        self.next = now() + self.period;
+        Ok(())
+    }
+
+    /// Wait for the period to elapse and shift the display by a word,
+    /// `texts` being the ones given to [`start()`](Marquee::start)
+    pub fn step(&mut self, texts: &[&[u8]]) -> Result<(), T::Error> {
+        sleep_until(self.next);
+        let rl = self.direction == Direction::Right;
+        self.lcd.cursor_display_ctrl(true, rl)?;
+        self.next = now() + self.period;
+
+        // The word moved around, and the word of the text it now shows
+        let (moved, index) = match self.direction {
+            Direction::Left => {
+                let moved = self.shift;
+                self.shift = (self.shift + 1) % ROW_WORDS;
+                self.position += 1;
+                (moved, self.position + ROW_WORDS as isize - 1)
+            }
+            Direction::Right => {
+                self.shift = (self.shift + ROW_WORDS - 1) % ROW_WORDS;
+                self.position -= 1;
+                (self.shift, self.position)
+            }
+        };
+        for (row, text) in texts.iter().take(2).enumerate() {
+            self.lcd.ddram_addr(row as u8 * 0x10 + moved)?;
+            self.lcd.write(word(text, index))?;
+        }
+        Ok(())
+    }
+
+    /// Go back to the home position
+    ///
+    /// The texts are left in the rows as they were moved around,
+    /// [`start()`](Marquee::start) shows their beginning again.
+    pub fn stop(&mut self) -> Result<(), T::Error> {
+        (self.shift, self.position) = (0, 0);
+        self.lcd.home()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::hal::clock;
+    use crate::mock::Emulator;
+
+    use super::*;
+
+    const TEXTS: [&[u8]; 2] = [b"ABCDEFGHIJKLMNOPQRSTUVWXYZ0123456789", b"short"];
+
+    /// Words of the given row shown on the screen, following the display shift
+    fn window(lcd: &Emulator, row: usize) -> [u16; ROW_WORDS as usize] {
+        let words = ROW_WORDS as isize;
+        core::array::from_fn(|i| {
+            let word = (i as isize - lcd.shift as isize).rem_euclid(words);
+            lcd.ddram[row * 0x10 + word as usize]
+        })
+    }
+
+    fn check_steps(direction: Direction, step: isize) {
+        clock::reset();
+        let period = Duration::millis(100);
+        let mut lcd = Emulator::new();
+        let mut marquee = Marquee::new(&mut lcd, direction, period);
+        marquee.start(&TEXTS).unwrap();
+
+        for n in 1..=20 {
+            let ((), time) = clock::measure(|| marquee.step(&TEXTS).unwrap());
+            assert!(time >= period);
+
+            let lcd = &*marquee.lcd;
+            assert_eq!(lcd.shift as isize, -step * n);
+            for (row, text) in TEXTS.into_iter().enumerate() {
+                let expected = core::array::from_fn(|i| word(text, step * n + i as isize));
+                assert_eq!(window(lcd, row), expected, "row {row} after {n} steps");
+            }
+        }
+    }
+
+    #[test]
+    fn text_moves_left() {
+        check_steps(Direction::Left, 1);
+    }
+
+    #[test]
+    fn text_moves_right() {
+        check_steps(Direction::Right, -1);
+    }
+}
@@ -0,0 +1,467 @@
+//! Mock pins, SPI and I2C buses for testing the interfaces on the host
+//!
+//! Every mock is created from a [`Recorder`], which keeps track of every
+//! transition of the signals together with the time it happened at,
+//! and can export them as a Value Change Dump (VCD) to inspect with a
+//! waveform viewer or the `trace` decoder.
+//!
+//! Time is taken from the virtual [`clock`], that [`hal::sleep()`](crate::hal::sleep)
+//! advances instantly, so the delays applied by the interfaces show up in
+//! the recording without having to wait for them.
+//! Transitions that happen within the same tick keep the order they happened in.
+
+use core::{cell::RefCell, convert::Infallible, fmt::Write};
+use std::{collections::VecDeque, rc::Rc, string::String, vec::Vec};
+
+use embedded_hal::delay::DelayNs;
+use embedded_hal::digital::{ErrorType, InputPin, OutputPin};
+use embedded_hal::i2c::{self, Operation};
+use embedded_hal::spi::{self, SpiBus};
+
+use crate::hal::{self, clock, Duration, InPin, Instant, OutPin};
+
+pub mod emulator;
+pub use emulator::Emulator;
+
+/// What changed on a signal
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Change {
+    /// The level of the signal changed
+    Level(bool),
+    /// The pin was switched to output (`true`) or input (`false`)
+    Output(bool),
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Event {
+    pub time: Instant,
+    /// Index of the signal, as returned by [`Recorder::signal()`]
+    pub signal: usize,
+    pub change: Change,
+}
+
+struct Signal {
+    name: String,
+    level: bool,
+    output: bool,
+    /// Levels driven by the other end, returned by the following reads
+    input: VecDeque<bool>,
+}
+
+#[derive(Default)]
+struct Trace {
+    signals: Vec<Signal>,
+    events: Vec<Event>,
+}
+
+impl Trace {
+    fn record(&mut self, signal: usize, change: Change) {
+        let s = &mut self.signals[signal];
+        match change {
+            Change::Level(level) if s.level == level => return,
+            Change::Output(output) if s.output == output => return,
+            Change::Level(level) => s.level = level,
+            Change::Output(output) => s.output = output,
+        }
+        self.events.push(Event {
+            time: clock::now(),
+            signal,
+            change,
+        });
+    }
+}
+
+/// Shared record of the transitions of every mocked signal
+#[derive(Clone, Default)]
+pub struct Recorder(Rc<RefCell<Trace>>);
+
+impl Recorder {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    fn add(&self, name: &str) -> usize {
+        let mut trace = self.0.borrow_mut();
+        trace.signals.push(Signal {
+            name: name.into(),
+            level: false,
+            output: true,
+            input: VecDeque::new(),
+        });
+        trace.signals.len() - 1
+    }
+
+    /// Create a new pin, configured as an output and set low
+    pub fn pin(&self, name: &str) -> Pin {
+        Pin {
+            recorder: self.clone(),
+            signal: self.add(name),
+        }
+    }
+
+    /// Create a new SPI bus, with the given clock period,
+    /// recording the `sclk` and `sid` (MOSI) signals
+    ///
+    /// The clock idles low and data is sampled on its rising edge.
+    pub fn spi(&self, sclk: &str, sid: &str, period: Duration) -> Spi {
+        Spi {
+            recorder: self.clone(),
+            sclk: self.add(sclk),
+            sid: self.add(sid),
+            period,
+        }
+    }
+
+    /// Index of the signal with the given name
+    pub fn signal(&self, name: &str) -> Option<usize> {
+        let trace = self.0.borrow();
+        trace.signals.iter().position(|s| s.name == name)
+    }
+
+    /// Queue levels that the other end drives on the pin,
+    /// each read consumes one of them
+    ///
+    /// When there are no more levels queued the pin reads as its last level.
+    pub fn drive(&self, name: &str, levels: impl IntoIterator<Item = bool>) {
+        let signal = self.signal(name).expect("unknown signal");
+        self.0.borrow_mut().signals[signal].input.extend(levels);
+    }
+
+    /// Every change recorded so far
+    pub fn events(&self) -> Vec<Event> {
+        self.0.borrow().events.clone()
+    }
+
+    /// Level changes of the given signal
+    pub fn transitions(&self, name: &str) -> Vec<(Instant, bool)> {
+        let signal = self.signal(name).expect("unknown signal");
+        let trace = self.0.borrow();
+        let events = trace.events.iter().filter(|e| e.signal == signal);
+        events
+            .filter_map(|e| match e.change {
+                Change::Level(level) => Some((e.time, level)),
+                Change::Output(_) => None,
+            })
+            .collect()
+    }
+
+    /// Bytes shifted out on `sid`, sampled on the rising edges of `sclk`
+    /// (most significant bit first)
+    pub fn shifted(&self, sclk: &str, sid: &str) -> Vec<u8> {
+        let [sclk, sid] = [sclk, sid].map(|n| self.signal(n).expect("unknown signal"));
+        let trace = self.0.borrow();
+
+        let mut level = false;
+        let mut bits = Vec::new();
+        for event in &trace.events {
+            match event.change {
+                Change::Level(l) if event.signal == sid => level = l,
+                Change::Level(true) if event.signal == sclk => bits.push(level),
+                _ => (),
+            }
+        }
+        bits.chunks_exact(8)
+            .map(|byte| byte.iter().fold(0, |out, &b| out << 1 | b as u8))
+            .collect()
+    }
+
+    /// Export the recording as a Value Change Dump
+    ///
+    /// Each signal is a `wire` with its own name, pins that changed
+    /// direction also have an `<name>_OE` wire that is high while
+    /// the pin is an output.
+    pub fn vcd(&self) -> String {
+        let trace = self.0.borrow();
+        let id = vcd_id;
+        let oe = |i: usize| id(trace.signals.len() + i);
+        let switches = |i: usize| {
+            let mut events = trace.events.iter();
+            events.any(|e| e.signal == i && matches!(e.change, Change::Output(_)))
+        };
+
+        let mut out = String::new();
+        out.push_str("$timescale 1us $end\n$scope module st7920 $end\n");
+        for (i, signal) in trace.signals.iter().enumerate() {
+            writeln!(out, "$var wire 1 {} {} $end", id(i), signal.name).unwrap();
+            if switches(i) {
+                writeln!(out, "$var wire 1 {} {}_OE $end", oe(i), signal.name).unwrap();
+            }
+        }
+        out.push_str("$upscope $end\n$enddefinitions $end\n#0\n$dumpvars\n");
+        for i in 0..trace.signals.len() {
+            // The initial state is the one every signal is created with
+            writeln!(out, "0{}", id(i)).unwrap();
+            if switches(i) {
+                writeln!(out, "1{}", oe(i)).unwrap();
+            }
+        }
+        out.push_str("$end\n");
+
+        let mut time = None;
+        for event in &trace.events {
+            if time != Some(event.time) {
+                time = Some(event.time);
+                writeln!(out, "#{}", event.time.ticks()).unwrap();
+            }
+            let (value, id) = match event.change {
+                Change::Level(level) => (level, id(event.signal)),
+                Change::Output(output) => (output, oe(event.signal)),
+            };
+            writeln!(out, "{}{id}", value as u8).unwrap();
+        }
+        out
+    }
+}
+
+/// Identifier code of the `i`-th VCD variable
+///
+/// Codes are made of the 94 printable ASCII characters (`!` to `~`),
+/// using as many as needed: `!` to `~`, then `!!`, `"!` and so on.
+fn vcd_id(mut i: usize) -> String {
+    let mut id = String::new();
+    loop {
+        id.push((b'!' + (i % 94) as u8) as char);
+        i /= 94;
+        if i == 0 {
+            return id;
+        }
+        i -= 1;
+    }
+}
+
+impl core::fmt::Debug for Recorder {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        let trace = self.0.borrow();
+        let names: Vec<_> = trace.signals.iter().map(|s| &s.name).collect();
+        let events = trace.events.len();
+        write!(f, "Recorder {{ signals: {names:?}, events: {events} }}")
+    }
+}
+
+// - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - -
+
+/// A mocked [`IoPin`](crate::hal::IoPin)
+pub struct Pin {
+    recorder: Recorder,
+    signal: usize,
+}
+
+impl Pin {
+    fn record(&mut self, change: Change) {
+        self.recorder.0.borrow_mut().record(self.signal, change)
+    }
+}
+
+impl ErrorType for Pin {
+    type Error = Infallible;
+}
+
+impl OutputPin for Pin {
+    fn set_low(&mut self) -> Result<(), Self::Error> {
+        self.record(Change::Level(false));
+        Ok(())
+    }
+
+    fn set_high(&mut self) -> Result<(), Self::Error> {
+        self.record(Change::Level(true));
+        Ok(())
+    }
+}
+
+impl InputPin for Pin {
+    fn is_high(&mut self) -> Result<bool, Self::Error> {
+        let mut trace = self.recorder.0.borrow_mut();
+        let signal = &mut trace.signals[self.signal];
+        if signal.output {
+            return Ok(signal.level);
+        }
+        let level = signal.input.pop_front().unwrap_or(signal.level);
+        trace.record(self.signal, Change::Level(level));
+        Ok(level)
+    }
+
+    fn is_low(&mut self) -> Result<bool, Self::Error> {
+        self.is_high().map(|high| !high)
+    }
+}
+
+impl OutPin for Pin {
+    fn set_as_output(&mut self) -> Result<(), Self::Error> {
+        self.record(Change::Output(true));
+        Ok(())
+    }
+}
+
+impl InPin for Pin {
+    fn set_as_input(&mut self) -> Result<(), Self::Error> {
+        self.record(Change::Output(false));
+        Ok(())
+    }
+}
+
+impl core::fmt::Debug for Pin {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        let trace = self.recorder.0.borrow();
+        write!(f, "Pin({})", trace.signals[self.signal].name)
+    }
+}
+
+// - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - -
+
+/// A mocked write-only [`SpiBus`], reads return zeroes
+///
+/// Every bit takes one clock period on the virtual [`clock`].
+pub struct Spi {
+    recorder: Recorder,
+    sclk: usize,
+    sid: usize,
+    period: Duration,
+}
+
+impl Spi {
+    fn shift(&mut self, byte: u8) {
+        let half = self.period / 2;
+        let mut trace = self.recorder.0.borrow_mut();
+        for bit in (0..8).rev() {
+            trace.record(self.sid, Change::Level(byte & 1 << bit != 0));
+            clock::advance(half);
+            trace.record(self.sclk, Change::Level(true));
+            clock::advance(self.period - half);
+            trace.record(self.sclk, Change::Level(false));
+        }
+    }
+}
+
+impl spi::ErrorType for Spi {
+    type Error = Infallible;
+}
+
+impl SpiBus for Spi {
+    fn read(&mut self, words: &mut [u8]) -> Result<(), Self::Error> {
+        words.iter_mut().for_each(|w| {
+            self.shift(0);
+            *w = 0;
+        });
+        Ok(())
+    }
+
+    fn write(&mut self, words: &[u8]) -> Result<(), Self::Error> {
+        words.iter().for_each(|&w| self.shift(w));
+        Ok(())
+    }
+
+    fn transfer(&mut self, read: &mut [u8], write: &[u8]) -> Result<(), Self::Error> {
+        self.write(write)?;
+        let extra = read.len().saturating_sub(write.len());
+        (0..extra).for_each(|_| self.shift(0));
+        read.fill(0);
+        Ok(())
+    }
+
+    fn transfer_in_place(&mut self, words: &mut [u8]) -> Result<(), Self::Error> {
+        self.write(words)?;
+        words.fill(0);
+        Ok(())
+    }
+
+    fn flush(&mut self) -> Result<(), Self::Error> {
+        Ok(())
+    }
+}
+
+// - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - -
+
+/// An operation of a transaction recorded by [`I2c`]
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum I2cOperation {
+    Write(Vec<u8>),
+    /// Read of the given number of bytes
+    Read(usize),
+}
+
+/// A mocked [`I2c`](i2c::I2c) bus, recording the address and operations
+/// of every transaction
+///
+/// Reads return the bytes queued with [`answer()`](I2c::answer),
+/// and zeroes when there are none left.
+#[derive(Default)]
+pub struct I2c {
+    pub transactions: Vec<(u8, Vec<I2cOperation>)>,
+    answers: VecDeque<u8>,
+}
+
+impl I2c {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Queue bytes returned by the following reads
+    pub fn answer(&mut self, bytes: impl IntoIterator<Item = u8>) {
+        self.answers.extend(bytes);
+    }
+
+    /// The bytes written by each transaction, ignoring the reads
+    pub fn writes(&self) -> Vec<Vec<u8>> {
+        let bytes = |operation: &I2cOperation| match operation {
+            I2cOperation::Write(bytes) => bytes.clone(),
+            I2cOperation::Read(_) => Vec::new(),
+        };
+        (self.transactions.iter())
+            .map(|(_, operations)| operations.iter().flat_map(bytes).collect())
+            .collect()
+    }
+}
+
+impl i2c::ErrorType for I2c {
+    type Error = Infallible;
+}
+
+impl i2c::I2c for I2c {
+    fn transaction(
+        &mut self,
+        address: u8,
+        operations: &mut [Operation<'_>],
+    ) -> Result<(), Self::Error> {
+        let recorded = operations.iter_mut().map(|operation| match operation {
+            Operation::Write(bytes) => I2cOperation::Write(bytes.to_vec()),
+            Operation::Read(buf) => {
+                buf.fill_with(|| self.answers.pop_front().unwrap_or(0));
+                I2cOperation::Read(buf.len())
+            }
+        });
+        let recorded = recorded.collect();
+        self.transactions.push((address, recorded));
+        Ok(())
+    }
+}
+
+// - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - -
+
+/// A [`DelayNs`] that sleeps on the virtual [`clock`],
+/// rounding up to the next microsecond
+pub struct Delay;
+
+impl DelayNs for Delay {
+    fn delay_ns(&mut self, ns: u32) {
+        hal::sleep(Duration::from_ticks(ns.div_ceil(1000) as u64));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashSet;
+
+    use super::*;
+
+    #[test]
+    fn vcd_ids_are_unique_and_printable() {
+        let ids: Vec<_> = (0..94 * 95 + 1).map(vcd_id).collect();
+        assert_eq!(ids[..3], ["!", "\"", "#"]);
+        assert_eq!(ids[93..96], ["~", "!!", "\"!"]);
+        assert_eq!(ids.last().unwrap(), "!!!");
+        assert!(ids
+            .iter()
+            .all(|id| id.bytes().all(|b| b.is_ascii_graphic())));
+        assert_eq!(ids.iter().collect::<HashSet<_>>().len(), ids.len());
+    }
+}
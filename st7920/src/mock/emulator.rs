@@ -0,0 +1,307 @@
+//! Command level emulation of the controller
+//!
+//! [`Emulator`] runs the [`Command`]s and [`ext::Command`]s sent to it on a
+//! model of the RAMs and of the state of the controller, to check what they
+//! leave on the display without going through a bus.
+//!
+//! Commands sent while the wrong instruction set is selected panic, since
+//! the controller would take them for something else.
+//! [`ScrollOffset`](ext::Command::ScrollOffset) and
+//! [`IconRamAddr`](ext::Command::IconRamAddr) share their encoding, so they
+//! are told apart by the last of [`EnableScroll`](ext::Command::EnableScroll)
+//! and [`EnableCgRam`](ext::Command::EnableCgRam), as the controller does.
+
+use core::convert::Infallible;
+
+use crate::icons::{self, IconSet};
+use crate::{ext, Command, Execute, ExecuteRead};
+
+/// RAM and address the Address Counter points to
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Address {
+    DdRam(u8),
+    CgRam(u8),
+    GraphicRam { x: u8, y: u8 },
+    IconRam(u8),
+}
+
+pub struct Emulator {
+    pub ddram: [u16; 64],
+    pub cgram: [u16; 64],
+    pub gdram: [[u16; 16]; 64],
+    pub iram: [u16; icons::WORDS],
+    pub address: Address,
+    /// Whether the _Extended instruction set_ is selected
+    pub extended: bool,
+    /// Whether the graphic display is on
+    pub graphic: bool,
+    /// Whether [`ScrollOffset`](ext::Command::ScrollOffset) sets the scroll
+    /// offset rather than the IRAM address
+    pub scroll: bool,
+    pub display: bool,
+    pub cursor: bool,
+    pub blink: bool,
+    /// Whether the Address Counter is incremented after each write
+    pub increment: bool,
+    /// Whether the display shifts after each write to the DDRAM
+    pub entry_shift: bool,
+    /// Display shift, in words to the right
+    pub shift: i8,
+    pub scroll_offset: u8,
+    /// The reversed line, if any
+    pub reversed: Option<u8>,
+    pub stand_by: bool,
+    /// Word returned by the next read
+    latch: u16,
+}
+
+impl Emulator {
+    /// A controller just after reset, with the display off
+    /// and the DDRAM filled with spaces
+    pub fn new() -> Self {
+        Self {
+            ddram: [0x2020; 64],
+            cgram: [0; 64],
+            gdram: [[0; 16]; 64],
+            iram: [0; icons::WORDS],
+            address: Address::DdRam(0),
+            extended: false,
+            graphic: false,
+            scroll: false,
+            display: false,
+            cursor: false,
+            blink: false,
+            increment: true,
+            entry_shift: false,
+            shift: 0,
+            scroll_offset: 0,
+            reversed: None,
+            stand_by: false,
+            latch: 0,
+        }
+    }
+
+    /// The icons turned on in the IRAM
+    pub fn icons(&self) -> IconSet {
+        IconSet { words: self.iram }
+    }
+
+    fn word(&mut self) -> &mut u16 {
+        match self.address {
+            Address::DdRam(addr) => &mut self.ddram[addr as usize],
+            Address::CgRam(addr) => &mut self.cgram[addr as usize],
+            Address::GraphicRam { x, y } => &mut self.gdram[y as usize][x as usize],
+            Address::IconRam(addr) => &mut self.iram[addr as usize],
+        }
+    }
+
+    /// Move the Address Counter after a RAM access
+    fn step(&mut self) {
+        let step = |addr: u8| match self.increment {
+            true => addr.wrapping_add(1),
+            false => addr.wrapping_sub(1),
+        };
+        self.address = match self.address {
+            Address::DdRam(addr) => Address::DdRam(step(addr) & 0b111111),
+            Address::CgRam(addr) => Address::CgRam(step(addr) & 0b111111),
+            Address::GraphicRam { x, y } => Address::GraphicRam {
+                x: x.wrapping_add(1) & 0b1111,
+                y,
+            },
+            // The last word is followed by the first one
+            Address::IconRam(addr) => Address::IconRam((addr + 1) % icons::WORDS as u8),
+        };
+    }
+
+    fn write_ram(&mut self, data: u16) {
+        *self.word() = data;
+        if let (Address::DdRam(_), true) = (self.address, self.entry_shift) {
+            self.shift += if self.increment { 1 } else { -1 };
+        }
+        self.step();
+    }
+}
+
+impl Default for Emulator {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+// - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - -
+
+impl Execute for Emulator {
+    type Error = Infallible;
+
+    fn execute(&mut self, command: Command) -> Result<(), Self::Error> {
+        use Command::*;
+        match command {
+            Write(data) => {
+                self.write_ram(data);
+                return Ok(());
+            }
+            SelectBasic => {
+                self.extended = false;
+                return Ok(());
+            }
+            _ => assert!(
+                !self.extended,
+                "{command:?} with the Extended instruction set"
+            ),
+        }
+        self.stand_by = false;
+        match command {
+            Clear => {
+                self.ddram = [0x2020; 64];
+                self.address = Address::DdRam(0);
+                self.increment = true;
+                self.entry_shift = false;
+                self.shift = 0;
+            }
+            Home => {
+                self.address = Address::DdRam(0);
+                self.shift = 0;
+            }
+            EntryMode { increment, shift } => {
+                self.increment = increment;
+                self.entry_shift = shift;
+            }
+            DisplayOnOff {
+                display,
+                cursor,
+                blink,
+            } => (self.display, self.cursor, self.blink) = (display, cursor, blink),
+            CursorDisplayCtrl { sc: true, rl } => self.shift += if rl { 1 } else { -1 },
+            CursorDisplayCtrl { sc: false, rl } => {
+                let Address::DdRam(addr) = self.address else {
+                    panic!("cursor moved outside of the DDRAM");
+                };
+                let addr = if rl {
+                    addr.wrapping_add(1)
+                } else {
+                    addr.wrapping_sub(1)
+                };
+                self.address = Address::DdRam(addr & 0b111111);
+            }
+            CgRamAddr(addr) => self.address = Address::CgRam(addr & 0b111111),
+            DdRamAddr(addr) => self.address = Address::DdRam(addr & 0b111111),
+            Write(_) | SelectBasic => unreachable!(),
+        }
+        Ok(())
+    }
+}
+
+impl ext::Execute for Emulator {
+    fn execute_ext(&mut self, command: ext::Command) -> Result<(), Self::Error> {
+        use ext::Command::*;
+        match command {
+            SelectExtended | SelectGraphic => {
+                self.extended = true;
+                self.graphic = command == SelectGraphic;
+                return Ok(());
+            }
+            _ => assert!(self.extended, "{command:?} with the Basic instruction set"),
+        }
+        match command {
+            StandBy => self.stand_by = true,
+            EnableScroll => self.scroll = true,
+            EnableCgRam => self.scroll = false,
+            Reverse(line) => {
+                self.reversed = match self.reversed {
+                    Some(_) => None,
+                    None => Some(line & 0b11),
+                }
+            }
+            ScrollOffset(_) | IconRamAddr(_) => {
+                let [byte, _] = command.into_bytes();
+                match self.scroll {
+                    true => self.scroll_offset = byte & 0b111111,
+                    false => {
+                        let addr = byte & 0b1111;
+                        assert!(
+                            addr < icons::WORDS as u8,
+                            "IRAM address {addr} out of range"
+                        );
+                        self.address = Address::IconRam(addr);
+                    }
+                }
+            }
+            GraphicRamAddr { y, x } => {
+                self.address = Address::GraphicRam {
+                    x: x & 0b1111,
+                    y: y & 0b111111,
+                }
+            }
+            SelectExtended | SelectGraphic => unreachable!(),
+        }
+        Ok(())
+    }
+}
+
+/// As on the controller, each read returns the word latched by the
+/// previous one, so the first read after setting the address is stale
+impl ExecuteRead for Emulator {
+    type Error = Infallible;
+
+    fn read(&mut self) -> Result<u16, Self::Error> {
+        assert!(
+            !matches!(self.address, Address::IconRam(_)),
+            "the IRAM can't be read"
+        );
+        let read = self.latch;
+        self.latch = *self.word();
+        self.step();
+        Ok(read)
+    }
+
+    fn read_bf_ac(&mut self) -> Result<(bool, u8), Self::Error> {
+        let ac = match self.address {
+            Address::DdRam(addr) | Address::CgRam(addr) | Address::IconRam(addr) => addr,
+            Address::GraphicRam { x, .. } => x,
+        };
+        Ok((false, ac))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::ext::Execute as _;
+
+    use super::*;
+
+    fn extended() -> Emulator {
+        let mut lcd = Emulator::new();
+        lcd.select_extended().unwrap();
+        lcd
+    }
+
+    #[test]
+    fn scroll_offset_and_iram_address_share_the_command() {
+        let mut lcd = extended();
+        lcd.enable_scroll().unwrap();
+        lcd.scroll_offset(5).unwrap();
+        assert_eq!((lcd.scroll_offset, lcd.address), (5, Address::DdRam(0)));
+
+        lcd.enable_cgram().unwrap();
+        lcd.icon_ram_addr(5).unwrap();
+        assert_eq!((lcd.scroll_offset, lcd.address), (5, Address::IconRam(5)));
+    }
+
+    #[test]
+    fn iram_wraps_after_the_last_word() {
+        let mut lcd = extended();
+        lcd.enable_cgram().unwrap();
+        lcd.icon_ram_addr(icons::WORDS as u8 - 1).unwrap();
+        lcd.write_burst(&[0x1234, 0x5678]).unwrap();
+        assert_eq!(lcd.iram[icons::WORDS - 1], 0x1234);
+        assert_eq!(lcd.iram[0], 0x5678);
+    }
+
+    #[test]
+    #[should_panic]
+    fn iram_address_past_the_last_word_panics() {
+        let mut lcd = extended();
+        lcd.enable_cgram().unwrap();
+        let _ = lcd.icon_ram_addr(icons::WORDS as u8);
+    }
+}
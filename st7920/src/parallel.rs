@@ -8,8 +8,17 @@
 
 use core::convert::identity;
 
+use crate::hal::{now, sleep_until, Instant};
+use crate::{ext, Command, Oscillator};
+
+pub mod expander;
 pub mod interface;
+pub mod probe;
+pub mod shift;
+pub mod timing;
 pub use interface::{Interface4Bit, Interface8Bit};
+pub use shift::ShiftRegister;
+pub use timing::Timing;
 
 /// A parallel bus interface to an ST7920 controlled LCD
 pub trait Control {
@@ -124,3 +133,106 @@ impl<T: Input> Input for &mut T {
         T::read_u8(self)
     }
 }
+
+// - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - -
+
+/// Set the data length (DL) bit of the function sets when the bus is 8-bit wide
+///
+/// [`Command::SelectBasic`] and the extended ones are all function sets,
+/// and sending one with the wrong DL switches the controller to the other bus width.
+fn function_set(byte: u8, eight_bit: bool) -> u8 {
+    match byte & 0b11100000 == 0b00100000 {
+        true => byte | (eight_bit as u8) << 4,
+        false => byte,
+    }
+}
+
+/// Select the right register and write the command to it
+///
+/// Waiting for the execution time of the previous command is left to the caller.
+pub(crate) fn execute<T: Output>(
+    bus: &mut T,
+    command: Command,
+    eight_bit: bool,
+) -> Result<(), T::Error> {
+    if let Command::Write(data) = command {
+        bus.select_ram_write()?;
+        return bus.write_u16(data);
+    }
+
+    bus.select_command()?;
+    bus.write_u8(function_set(command.into_byte(), eight_bit))
+}
+
+/// Like [`execute()`] for the extended instruction set
+pub(crate) fn execute_ext<T: Output>(
+    bus: &mut T,
+    command: ext::Command,
+    eight_bit: bool,
+) -> Result<(), T::Error> {
+    bus.select_command()?;
+    let [first, second] = command.into_bytes();
+    bus.write_u8(function_set(first, eight_bit))?;
+    if second != 0 {
+        bus.write_u8(second)?;
+    }
+    Ok(())
+}
+
+pub(crate) fn read_bf_ac<T: Input>(bus: &mut T) -> Result<(bool, u8), T::Error> {
+    bus.select_bf_ac()?;
+    let read = bus.read_u8()?;
+    Ok((read & 0b10000000 != 0, read & 0b01111111))
+}
+
+pub(crate) fn read<T: Input>(bus: &mut T) -> Result<u16, T::Error> {
+    bus.select_ram_read()?;
+    bus.read_u16()
+}
+
+/// A bus that keeps track of when the controller will be done with
+/// the last instruction, to implement [`Execute`](crate::Execute) with
+/// the functions below
+pub(crate) trait Paced {
+    fn oscillator(&self) -> &Oscillator;
+    /// When the controller can receive the next instruction
+    fn end(&mut self) -> &mut Instant;
+}
+
+/// Wait for the previous instruction, then [`execute()`] the command
+/// and record its execution time
+pub(crate) fn execute_paced<T: Output + Paced>(
+    bus: &mut T,
+    command: Command,
+    eight_bit: bool,
+) -> Result<(), T::Error> {
+    sleep_until(*bus.end());
+    execute(bus, command, eight_bit)?;
+    *bus.end() = now() + command.execution_time_at(bus.oscillator());
+    Ok(())
+}
+
+/// Like [`execute_paced()`] for the extended instruction set
+pub(crate) fn execute_ext_paced<T: Output + Paced>(
+    bus: &mut T,
+    command: ext::Command,
+    eight_bit: bool,
+) -> Result<(), T::Error> {
+    sleep_until(*bus.end());
+    execute_ext(bus, command, eight_bit)?;
+    *bus.end() = now() + command.execution_time_at(bus.oscillator());
+    Ok(())
+}
+
+pub(crate) fn read_bf_ac_paced<T: Input + Paced>(bus: &mut T) -> Result<(bool, u8), T::Error> {
+    sleep_until(*bus.end());
+    read_bf_ac(bus)
+}
+
+pub(crate) fn read_paced<T: Input + Paced>(bus: &mut T) -> Result<u16, T::Error> {
+    sleep_until(*bus.end());
+    let read = read(bus)?;
+    // Reading the RAM takes as long as writing it
+    *bus.end() = now() + Command::Write(read).execution_time_at(bus.oscillator());
+    Ok(read)
+}
@@ -0,0 +1,17 @@
+//! Parallel bus driven through an I2C port expander
+//!
+//! Many ST7920 modules come with an I2C backpack: a [`Pcf8574`] driving
+//! RS, RW, E and DB4-7 in 4-bit mode, or a [`Mcp23017`] driving the whole
+//! 8-bit bus on one port and the control signals on the other.
+//!
+//! Each byte on the I2C bus takes several microseconds even in fast mode plus,
+//! which is longer than any of the [`Timing`](super::Timing) parameters,
+//! so no additional wait is needed between the pin changes.
+//! To keep the number of transactions low, the pin changes needed to latch
+//! each nibble (or byte) of a transfer are written back to back in a
+//! single transaction.
+
+pub mod mcp23017;
+pub mod pcf8574;
+pub use mcp23017::Mcp23017;
+pub use pcf8574::Pcf8574;
@@ -0,0 +1,287 @@
+//! 8-bit bus through a MCP23017
+//!
+//! DB0-7 are connected to port A, while RS, RW and E are connected to port B.
+//!
+//! The expander is configured in byte mode with `IOCON.BANK = 0`, where the
+//! register pointer toggles between the A and B registers of a pair after
+//! each byte: writing `GPIOA, data, control, data, control, ...` sets the
+//! data bus and then moves E, all in the same transaction.
+
+use embedded_hal::i2c::{I2c, Operation};
+
+use crate::hal::{now, Instant};
+use crate::parallel::{self, Control, Input, Output, Paced};
+use crate::{ext, Command, Execute, ExecuteRead, Oscillator};
+
+const IODIRA: u8 = 0x00;
+const IOCON: u8 = 0x0A;
+const GPIOA: u8 = 0x12;
+const GPIOB: u8 = 0x13;
+
+/// Sequential operation disabled, that is byte mode
+const IOCON_SEQOP: u8 = 1 << 5;
+
+/// Which bit of port B is connected to each signal
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Layout {
+    pub rs: u8,
+    pub rw: u8,
+    pub e: u8,
+    /// Transistor driving the backlight, if any
+    pub backlight: Option<u8>,
+}
+
+impl Layout {
+    /// RS, RW, E and the backlight on GPB0-3
+    pub const COMMON: Self = Self {
+        rs: 0,
+        rw: 1,
+        e: 2,
+        backlight: Some(3),
+    };
+
+    fn backlight(&self) -> u8 {
+        self.backlight.map_or(0, |bit| 1 << bit)
+    }
+
+    fn outputs(&self) -> u8 {
+        1 << self.rs | 1 << self.rw | 1 << self.e | self.backlight()
+    }
+}
+
+impl Default for Layout {
+    fn default() -> Self {
+        Self::COMMON
+    }
+}
+
+pub struct Mcp23017<I> {
+    i2c: I,
+    address: u8,
+    layout: Layout,
+    /// Last value written to port B
+    control: u8,
+    /// Whether port A is configured as input
+    input: bool,
+    oscillator: Oscillator,
+    end: Instant,
+}
+
+impl<I> Mcp23017<I> {
+    /// Compute the execution times for the given oscillator
+    /// instead of the [nominal](Oscillator::NOMINAL) one
+    pub fn with_oscillator(mut self, oscillator: Oscillator) -> Self {
+        self.oscillator = oscillator;
+        self
+    }
+
+    pub fn release(self) -> I {
+        self.i2c
+    }
+}
+
+impl<I: I2c> Mcp23017<I> {
+    /// Configure the expander at the given (7-bit) address
+    ///
+    /// Port A is set as output, and so are the pins of port B in the layout,
+    /// the other ones are left as inputs. The backlight is turned on.
+    pub fn new(mut i2c: I, address: u8, layout: Layout) -> Result<Self, I::Error> {
+        let control = layout.backlight();
+        i2c.write(address, &[IOCON, IOCON_SEQOP])?;
+        i2c.write(address, &[IODIRA, 0x00, !layout.outputs()])?;
+        i2c.write(address, &[GPIOA, 0x00, control])?;
+        Ok(Self {
+            i2c,
+            address,
+            layout,
+            control,
+            input: false,
+            oscillator: Oscillator::default(),
+            end: now(),
+        })
+    }
+
+    fn write_control(&mut self, control: u8) -> Result<(), I::Error> {
+        self.i2c.write(self.address, &[GPIOB, control])?;
+        self.control = control;
+        Ok(())
+    }
+
+    pub fn set_backlight(&mut self, on: bool) -> Result<(), I::Error> {
+        let mask = self.layout.backlight();
+        let control = match on {
+            true => self.control | mask,
+            false => self.control & !mask,
+        };
+        self.write_control(control)
+    }
+
+    pub fn set_as_output(&mut self) -> Result<(), I::Error> {
+        if self.input {
+            self.i2c.write(self.address, &[IODIRA, 0x00])?;
+            self.input = false;
+        }
+        Ok(())
+    }
+
+    pub fn set_as_input(&mut self) -> Result<(), I::Error> {
+        if !self.input {
+            self.i2c.write(self.address, &[IODIRA, 0xFF])?;
+            self.input = true;
+        }
+        Ok(())
+    }
+
+    /// Latch the given bytes, raising and lowering E for each one,
+    /// in a single transaction
+    fn write_bytes<const N: usize>(&mut self, data: [u8; N]) -> Result<(), I::Error> {
+        self.set_as_output()?;
+        let (control, e) = (self.control, 1 << self.layout.e);
+        let mut buf = [GPIOA; 9];
+        for (bytes, data) in buf[1..].chunks_exact_mut(4).zip(data) {
+            bytes.copy_from_slice(&[data, control | e, data, control]);
+        }
+        self.i2c.write(self.address, &buf[..1 + 4 * N])
+    }
+}
+
+// - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - -
+
+impl<I: I2c> Control for Mcp23017<I> {
+    type Error = I::Error;
+
+    fn enable(&mut self) -> Result<(), Self::Error> {
+        self.write_control(self.control | 1 << self.layout.e)
+    }
+
+    fn disable(&mut self) -> Result<(), Self::Error> {
+        self.write_control(self.control & !(1 << self.layout.e))
+    }
+
+    fn select(&mut self, rs: bool, rw: bool) -> Result<(), Self::Error> {
+        let layout = self.layout;
+        let mask = 1 << layout.rs | 1 << layout.rw;
+        let control = self.control & !mask | (rs as u8) << layout.rs | (rw as u8) << layout.rw;
+        match control == self.control {
+            true => Ok(()),
+            false => self.write_control(control),
+        }
+    }
+}
+
+impl<I: I2c> Output for Mcp23017<I> {
+    fn write_u8(&mut self, data: u8) -> Result<(), Self::Error> {
+        self.write_bytes([data])
+    }
+
+    fn write_u16(&mut self, data: u16) -> Result<(), Self::Error> {
+        self.write_bytes(data.to_be_bytes())
+    }
+}
+
+impl<I: I2c> Input for Mcp23017<I> {
+    /// Raise E, read port A and lower E in a single transaction
+    ///
+    /// Writing E to `GPIOB` moves the pointer to `GPIOA`,
+    /// so the read that follows returns the data bus.
+    fn read_u8(&mut self) -> Result<u8, Self::Error> {
+        self.set_as_input()?;
+        let (control, e) = (self.control, 1 << self.layout.e);
+        let mut data = [0];
+        self.i2c.transaction(
+            self.address,
+            &mut [
+                Operation::Write(&[GPIOB, control | e]),
+                Operation::Read(&mut data),
+                Operation::Write(&[GPIOB, control]),
+            ],
+        )?;
+        Ok(data[0])
+    }
+}
+
+// - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - -
+
+impl<I: I2c> Paced for Mcp23017<I> {
+    fn oscillator(&self) -> &Oscillator {
+        &self.oscillator
+    }
+
+    fn end(&mut self) -> &mut Instant {
+        &mut self.end
+    }
+}
+
+impl<I: I2c> Execute for Mcp23017<I> {
+    type Error = I::Error;
+
+    fn execute(&mut self, command: Command) -> Result<(), Self::Error> {
+        parallel::execute_paced(self, command, true)
+    }
+}
+
+impl<I: I2c> ext::Execute for Mcp23017<I> {
+    fn execute_ext(&mut self, command: ext::Command) -> Result<(), Self::Error> {
+        parallel::execute_ext_paced(self, command, true)
+    }
+}
+
+impl<I: I2c> ExecuteRead for Mcp23017<I> {
+    type Error = I::Error;
+
+    fn read_bf_ac(&mut self) -> Result<(bool, u8), Self::Error> {
+        parallel::read_bf_ac_paced(self)
+    }
+
+    fn read(&mut self) -> Result<u16, Self::Error> {
+        parallel::read_paced(self)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::vec;
+    use std::vec::Vec;
+
+    use crate::mock::{I2c, I2cOperation};
+
+    use super::*;
+
+    const ADDRESS: u8 = 0x20;
+
+    #[test]
+    fn port_a_switches_direction_only_when_needed() {
+        let mut i2c = I2c::new();
+        i2c.answer([0x92, 0x12]);
+        let mut lcd = Mcp23017::new(i2c, ADDRESS, Layout::COMMON).unwrap();
+        lcd.clear().unwrap();
+        assert_eq!(lcd.read_bf_ac().unwrap(), (true, 0x12));
+        assert_eq!(lcd.read_bf_ac().unwrap(), (false, 0x12));
+        lcd.write(0x4142).unwrap();
+
+        let i2c = lcd.release();
+        let write = |bytes: &[u8]| vec![I2cOperation::Write(bytes.to_vec())];
+        let read = vec![
+            I2cOperation::Write(vec![GPIOB, 0x0E]),
+            I2cOperation::Read(1),
+            I2cOperation::Write(vec![GPIOB, 0x0A]),
+        ];
+        let expected = [
+            // Port B outputs and the backlight on
+            write(&[IOCON, IOCON_SEQOP]),
+            write(&[IODIRA, 0x00, 0xF0]),
+            write(&[GPIOA, 0x00, 0x08]),
+            // RS and RW are already low for the command
+            write(&[GPIOA, 0x01, 0x0C, 0x01, 0x08]),
+            write(&[GPIOB, 0x0A]),
+            write(&[IODIRA, 0xFF]),
+            read.clone(),
+            read,
+            write(&[GPIOB, 0x09]),
+            write(&[IODIRA, 0x00]),
+            write(&[GPIOA, 0x41, 0x0D, 0x41, 0x09, 0x42, 0x0D, 0x42, 0x09]),
+        ];
+        let transactions: Vec<_> = i2c.transactions.into_iter().map(|(_, ops)| ops).collect();
+        assert_eq!(transactions, expected);
+    }
+}
@@ -0,0 +1,287 @@
+//! 4-bit bus through a PCF8574
+//!
+//! The PCF8574 has no direction register: its pins are quasi-bidirectional,
+//! so writing a one leaves the pin weakly pulled up, and the LCD can drive it
+//! low when reading.
+
+use embedded_hal::i2c::{I2c, Operation};
+
+use crate::hal::{now, Instant};
+use crate::parallel::{self, Control, Input, Output, Paced};
+use crate::{ext, Command, Execute, ExecuteRead, Oscillator};
+
+/// Which bit of the port is connected to each signal
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Layout {
+    pub rs: u8,
+    pub rw: u8,
+    pub e: u8,
+    /// Transistor driving the backlight, if any
+    pub backlight: Option<u8>,
+    /// DB4 to DB7
+    pub data: [u8; 4],
+}
+
+impl Layout {
+    /// The wiring of the most common backpacks:
+    /// RS, RW, E and the backlight on P0-3, and DB4-7 on P4-7
+    pub const COMMON: Self = Self {
+        rs: 0,
+        rw: 1,
+        e: 2,
+        backlight: Some(3),
+        data: [4, 5, 6, 7],
+    };
+
+    fn backlight(&self) -> u8 {
+        self.backlight.map_or(0, |bit| 1 << bit)
+    }
+
+    fn encode(&self, nibble: u8) -> u8 {
+        (self.data.iter().enumerate())
+            .filter(|&(i, _)| nibble & (1 << i) != 0)
+            .fold(0, |port, (_, bit)| port | 1 << bit)
+    }
+
+    fn decode(&self, port: u8) -> u8 {
+        (self.data.iter().enumerate())
+            .filter(|&(_, bit)| port & (1 << bit) != 0)
+            .fold(0, |nibble, (i, _)| nibble | 1 << i)
+    }
+}
+
+impl Default for Layout {
+    fn default() -> Self {
+        Self::COMMON
+    }
+}
+
+pub struct Pcf8574<I> {
+    i2c: I,
+    address: u8,
+    layout: Layout,
+    /// Last value written to the port
+    port: u8,
+    oscillator: Oscillator,
+    end: Instant,
+}
+
+impl<I> Pcf8574<I> {
+    /// Create the interface for the expander at the given (7-bit) address
+    ///
+    /// The port is only written by the first operation, with the backlight on.
+    pub fn new(i2c: I, address: u8, layout: Layout) -> Self {
+        Self {
+            i2c,
+            address,
+            port: layout.backlight(),
+            layout,
+            oscillator: Oscillator::default(),
+            end: now(),
+        }
+    }
+
+    /// Compute the execution times for the given oscillator
+    /// instead of the [nominal](Oscillator::NOMINAL) one
+    pub fn with_oscillator(mut self, oscillator: Oscillator) -> Self {
+        self.oscillator = oscillator;
+        self
+    }
+
+    pub fn release(self) -> I {
+        self.i2c
+    }
+
+    fn bit(&self, bit: u8, on: bool) -> u8 {
+        (on as u8) << bit
+    }
+
+    /// The port with the control signals as they are, and the data pins low
+    fn control(&self) -> u8 {
+        let layout = &self.layout;
+        self.port & (1 << layout.rs | 1 << layout.rw | layout.backlight())
+    }
+}
+
+impl<I: I2c> Pcf8574<I> {
+    fn write_port(&mut self, port: u8) -> Result<(), I::Error> {
+        self.i2c.write(self.address, &[port])?;
+        self.port = port;
+        Ok(())
+    }
+
+    pub fn set_backlight(&mut self, on: bool) -> Result<(), I::Error> {
+        let mask = self.layout.backlight();
+        let port = match on {
+            true => self.port | mask,
+            false => self.port & !mask,
+        };
+        self.write_port(port)
+    }
+
+    /// Latch the given nibbles, raising and lowering E for each one,
+    /// in a single transaction
+    fn write_nibbles<const N: usize>(&mut self, nibbles: [u8; N]) -> Result<(), I::Error> {
+        let e = 1 << self.layout.e;
+        let mut buf = [0; 8];
+        for (bytes, nibble) in buf.chunks_exact_mut(2).zip(nibbles) {
+            let port = self.control() | self.layout.encode(nibble);
+            bytes.copy_from_slice(&[port | e, port]);
+        }
+        self.i2c.write(self.address, &buf[..2 * N])?;
+        self.port = buf[2 * N - 1];
+        Ok(())
+    }
+}
+
+// - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - -
+
+impl<I: I2c> Control for Pcf8574<I> {
+    type Error = I::Error;
+
+    fn enable(&mut self) -> Result<(), Self::Error> {
+        self.write_port(self.port | 1 << self.layout.e)
+    }
+
+    fn disable(&mut self) -> Result<(), Self::Error> {
+        self.write_port(self.port & !(1 << self.layout.e))
+    }
+
+    fn select(&mut self, rs: bool, rw: bool) -> Result<(), Self::Error> {
+        let layout = self.layout;
+        let mask = 1 << layout.rs | 1 << layout.rw;
+        let port = self.port & !mask | self.bit(layout.rs, rs) | self.bit(layout.rw, rw);
+        match port == self.port {
+            true => Ok(()),
+            false => self.write_port(port),
+        }
+    }
+}
+
+impl<I: I2c> Output for Pcf8574<I> {
+    fn write_u8(&mut self, data: u8) -> Result<(), Self::Error> {
+        self.write_nibbles([data >> 4, data & 0xF])
+    }
+
+    fn write_u16(&mut self, data: u16) -> Result<(), Self::Error> {
+        let [h, l] = data.to_be_bytes();
+        self.write_nibbles([h >> 4, h & 0xF, l >> 4, l & 0xF])
+    }
+}
+
+impl<I: I2c> Input for Pcf8574<I> {
+    /// Read both nibbles in a single transaction, with the data pins released
+    fn read_u8(&mut self) -> Result<u8, Self::Error> {
+        let port = self.control() | self.layout.encode(0xF);
+        let e = 1 << self.layout.e;
+        let (mut h, mut l) = ([0], [0]);
+        self.i2c.transaction(
+            self.address,
+            &mut [
+                Operation::Write(&[port | e]),
+                Operation::Read(&mut h),
+                Operation::Write(&[port, port | e]),
+                Operation::Read(&mut l),
+                Operation::Write(&[port]),
+            ],
+        )?;
+        self.port = port;
+
+        let layout = &self.layout;
+        Ok(layout.decode(h[0]) << 4 | layout.decode(l[0]))
+    }
+}
+
+// - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - -
+
+impl<I: I2c> Paced for Pcf8574<I> {
+    fn oscillator(&self) -> &Oscillator {
+        &self.oscillator
+    }
+
+    fn end(&mut self) -> &mut Instant {
+        &mut self.end
+    }
+}
+
+impl<I: I2c> Execute for Pcf8574<I> {
+    type Error = I::Error;
+
+    fn execute(&mut self, command: Command) -> Result<(), Self::Error> {
+        parallel::execute_paced(self, command, false)
+    }
+}
+
+impl<I: I2c> ext::Execute for Pcf8574<I> {
+    fn execute_ext(&mut self, command: ext::Command) -> Result<(), Self::Error> {
+        parallel::execute_ext_paced(self, command, false)
+    }
+}
+
+impl<I: I2c> ExecuteRead for Pcf8574<I> {
+    type Error = I::Error;
+
+    fn read_bf_ac(&mut self) -> Result<(bool, u8), Self::Error> {
+        parallel::read_bf_ac_paced(self)
+    }
+
+    fn read(&mut self) -> Result<u16, Self::Error> {
+        parallel::read_paced(self)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::vec;
+    use std::vec::Vec;
+
+    use crate::mock::{I2c, I2cOperation};
+
+    use super::*;
+
+    const ADDRESS: u8 = 0x27;
+
+    #[test]
+    fn nibbles_are_latched_in_a_single_write() {
+        let mut lcd = Pcf8574::new(I2c::new(), ADDRESS, Layout::COMMON);
+        lcd.ddram_addr(0x12).unwrap();
+        lcd.write(0xa5c3).unwrap();
+
+        // E is P2 and the backlight P3, DB4-7 are P4-7
+        let i2c = lcd.release();
+        let command = vec![0x9C, 0x98, 0x2C, 0x28];
+        // RS goes high on its own, the data pins keeping the last nibble
+        let select = vec![0x29];
+        let data = vec![0xAD, 0xA9, 0x5D, 0x59, 0xCD, 0xC9, 0x3D, 0x39];
+        assert_eq!(i2c.writes(), [command, select, data]);
+        assert!(i2c
+            .transactions
+            .iter()
+            .all(|(address, _)| *address == ADDRESS));
+    }
+
+    #[test]
+    fn reads_release_the_data_pins() {
+        let mut i2c = I2c::new();
+        // DB4-7 on the high nibble of the port, the other pins read back high
+        i2c.answer([0x9F, 0x2F]);
+        let mut lcd = Pcf8574::new(i2c, ADDRESS, Layout::COMMON);
+        assert_eq!(lcd.read_bf_ac().unwrap(), (true, 0x12));
+        lcd.clear().unwrap();
+
+        let i2c = lcd.release();
+        let read = vec![
+            I2cOperation::Write(vec![0xFE]),
+            I2cOperation::Read(1),
+            I2cOperation::Write(vec![0xFA, 0xFE]),
+            I2cOperation::Read(1),
+            I2cOperation::Write(vec![0xFA]),
+        ];
+        let select = |port| vec![I2cOperation::Write(vec![port])];
+        // The data pins are driven low again with the next nibbles
+        let clear = vec![I2cOperation::Write(vec![0x0C, 0x08, 0x1C, 0x18])];
+        let expected = [select(0x0A), read, select(0xF8), clear];
+        let transactions: Vec<_> = i2c.transactions.into_iter().map(|(_, ops)| ops).collect();
+        assert_eq!(transactions, expected);
+    }
+}
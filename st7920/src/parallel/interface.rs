@@ -1,62 +1,192 @@
+use core::ops::{Deref, DerefMut};
+
 use embedded_hal::digital::OutputPin;
-use fugit::ExtU64;
 
-use crate::hal::{now, sleep, sleep_until, InPin, Instant, IoPin, OutPin};
-use crate::{ext, Command, Execute, ExecuteRead, SharedBus};
+use crate::hal::{at_least, now, sleep, sleep_until, Instant, OutPort, ParallelPort};
+use crate::{ext, Command, Execute, ExecuteRead, Oscillator, SharedBus};
 
-use super::{Control, Input, Output};
+use super::timing::{Deadlines, Timing};
+use super::{Control, Input, Output, Paced};
 
 struct Pin<E> {
     e: E,
     end: Instant,
 }
 
-pub struct Interface<Out, InOut, const PINS: usize, const BITS: usize> {
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum Direction {
+    Input,
+    Output,
+}
+
+/// Parallel interface driving the enable signals and RS/RW with separate pins
+///
+/// The data bus is either an array of pins, accessed one at the time,
+/// or an [`OutPort`] (a [`ParallelPort`] to read too) accessing all of them at once.
+pub struct Interface<Out, Bus, const PINS: usize, const BITS: usize> {
     rs: Out,
     rw: Out,
     pins: [Pin<Out>; PINS],
-    bus: [InOut; BITS],
+    bus: Bus,
+    /// Current direction of the data bus, `None` when unknown
+    direction: Option<Direction>,
+    timing: Timing,
+    oscillator: Oscillator,
+    next: Deadlines,
 }
 
-impl<O, Io, const PINS: usize, const BITS: usize> Interface<O, Io, PINS, BITS> {
-    pub fn new(rs: O, rw: O, e: [O; PINS], bus: [Io; BITS]) -> Self {
+impl<O, B, const PINS: usize, const BITS: usize> Interface<O, B, PINS, BITS> {
+    /// The data bus must be as wide as the interface, `BITS` pins
+    pub fn new(rs: O, rw: O, e: [O; PINS], bus: B, timing: Timing) -> Self
+    where
+        B: OutPort,
+    {
+        const { assert!(B::WIDTH == BITS, "the data bus must have BITS pins") };
         let end = now();
         let pins = e.map(|e| Pin { e, end });
-        Self { rs, rw, pins, bus }
+        Self {
+            rs,
+            rw,
+            pins,
+            bus,
+            direction: None,
+            timing,
+            oscillator: Oscillator::default(),
+            next: Deadlines::new(),
+        }
+    }
+
+    /// Compute the execution times for the given oscillator
+    /// instead of the [nominal](Oscillator::NOMINAL) one
+    pub fn with_oscillator(mut self, oscillator: Oscillator) -> Self {
+        self.oscillator = oscillator;
+        self
+    }
+
+    pub fn timing(&self) -> &Timing {
+        &self.timing
+    }
+
+    pub fn release(self) -> (O, O, [O; PINS], B) {
+        (self.rs, self.rw, self.pins.map(|pin| pin.e), self.bus)
     }
 }
 
-impl<O, Io: Copy, const PINS: usize, const BITS: usize> SharedBus for Interface<O, Io, PINS, BITS> {
+impl<O, B, const PINS: usize, const BITS: usize> SharedBus for Interface<O, B, PINS, BITS> {
     type Interface<'a>
-        = Interface<&'a mut O, &'a mut Io, 1, BITS>
+        = Shared<'a, O, B, BITS>
     where
         O: 'a,
-        Io: 'a;
+        B: 'a;
 
     fn num(&self) -> usize {
         PINS
     }
 
     fn get(&mut self, idx: usize) -> Option<Self::Interface<'_>> {
-        self.pins.get_mut(idx).map(|Pin { e, end }| Interface {
-            rs: &mut self.rs,
-            rw: &mut self.rw,
-            bus: self.bus.each_mut(),
-            pins: [Pin { e, end: *end }],
+        self.pins.get_mut(idx).map(|Pin { e, end }| Shared {
+            lcd: Interface {
+                rs: &mut self.rs,
+                rw: &mut self.rw,
+                bus: &mut self.bus,
+                direction: self.direction,
+                pins: [Pin { e, end: *end }],
+                timing: self.timing,
+                oscillator: self.oscillator,
+                next: self.next,
+            },
+            end,
+            direction: &mut self.direction,
+            next: &mut self.next,
         })
     }
 }
 
+/// One of the displays of an [`Interface`] shared between many of them,
+/// as given by [`SharedBus::get()`]
+///
+/// The state of the bus (its direction and when the next edges can happen)
+/// and the end of the last instruction are stored back into the shared
+/// [`Interface`] when this is dropped, so they carry over to the next
+/// display that is accessed.
+pub struct Shared<'a, O, B, const BITS: usize> {
+    lcd: Interface<&'a mut O, &'a mut B, 1, BITS>,
+    end: &'a mut Instant,
+    direction: &'a mut Option<Direction>,
+    next: &'a mut Deadlines,
+}
+
+impl<'a, O, B, const BITS: usize> Deref for Shared<'a, O, B, BITS> {
+    type Target = Interface<&'a mut O, &'a mut B, 1, BITS>;
+
+    fn deref(&self) -> &Self::Target {
+        &self.lcd
+    }
+}
+
+impl<O, B, const BITS: usize> DerefMut for Shared<'_, O, B, BITS> {
+    fn deref_mut(&mut self) -> &mut Self::Target {
+        &mut self.lcd
+    }
+}
+
+impl<O, B, const BITS: usize> Drop for Shared<'_, O, B, BITS> {
+    fn drop(&mut self) {
+        *self.end = self.lcd.pins[0].end;
+        *self.direction = self.lcd.direction;
+        *self.next = self.lcd.next;
+    }
+}
+
+impl<'a, O, B, const BITS: usize> Execute for Shared<'a, O, B, BITS>
+where
+    Interface<&'a mut O, &'a mut B, 1, BITS>: Execute,
+{
+    type Error = <Interface<&'a mut O, &'a mut B, 1, BITS> as Execute>::Error;
+
+    fn execute(&mut self, command: Command) -> Result<(), Self::Error> {
+        self.lcd.execute(command)
+    }
+}
+
+impl<'a, O, B, const BITS: usize> ext::Execute for Shared<'a, O, B, BITS>
+where
+    Interface<&'a mut O, &'a mut B, 1, BITS>: ext::Execute,
+{
+    fn execute_ext(&mut self, command: ext::Command) -> Result<(), Self::Error> {
+        self.lcd.execute_ext(command)
+    }
+}
+
+impl<'a, O, B, const BITS: usize> ExecuteRead for Shared<'a, O, B, BITS>
+where
+    Interface<&'a mut O, &'a mut B, 1, BITS>: ExecuteRead,
+{
+    type Error = <Interface<&'a mut O, &'a mut B, 1, BITS> as ExecuteRead>::Error;
+
+    fn read_bf_ac(&mut self) -> Result<(bool, u8), Self::Error> {
+        self.lcd.read_bf_ac()
+    }
+
+    fn read(&mut self) -> Result<u16, Self::Error> {
+        self.lcd.read()
+    }
+}
+
 // - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - -
 
-impl<O: OutputPin, Io, const BITS: usize> Control for Interface<O, Io, 1, BITS> {
+impl<O: OutputPin, B, const BITS: usize> Control for Interface<O, B, 1, BITS> {
     type Error = O::Error;
 
     fn enable(&mut self) -> Result<(), Self::Error> {
-        self.pins[0].e.set_high()
+        self.next.wait_rise();
+        self.pins[0].e.set_high()?;
+        self.next.rose(&self.timing);
+        Ok(())
     }
 
     fn disable(&mut self) -> Result<(), Self::Error> {
+        self.next.wait_fall();
         self.pins[0].e.set_low()?;
         Ok(())
     }
@@ -64,59 +194,118 @@ impl<O: OutputPin, Io, const BITS: usize> Control for Interface<O, Io, 1, BITS>
     fn select(&mut self, rs: bool, rw: bool) -> Result<(), Self::Error> {
         self.rs.set_state(rs.into())?;
         self.rw.set_state(rw.into())?;
+        self.next.selected(&self.timing);
         Ok(())
     }
 }
 
 // - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - -
 
-impl<O, Io: OutPin, const P: usize, const BITS: usize> Interface<O, Io, P, BITS> {
-    pub fn set_as_output(&mut self) -> Result<(), Io::Error> {
-        self.bus.iter_mut().try_for_each(OutPin::set_as_output)
+impl<O, B: OutPort, const P: usize, const BITS: usize> Interface<O, B, P, BITS> {
+    /// Switch the data bus to output, unless it already is
+    pub fn set_as_output(&mut self) -> Result<(), B::Error> {
+        if self.direction != Some(Direction::Output) {
+            self.direction = None;
+            self.bus.set_as_output()?;
+            self.direction = Some(Direction::Output);
+        }
+        Ok(())
     }
 
-    fn write_bus(&mut self, data: u8) -> Result<(), Io::Error> {
-        for (i, pin) in self.bus.iter_mut().enumerate() {
-            let level = data & (1 << i) != 0;
-            pin.set_state(level.into())?
-        }
+    fn write_bus(&mut self, data: u8) -> Result<(), B::Error> {
+        self.bus.write(data)?;
+        self.next.written(&self.timing);
         Ok(())
     }
 }
 
-impl<O, Io: IoPin, const P: usize, const BITS: usize> Interface<O, Io, P, BITS> {
-    pub fn set_as_input(&mut self) -> Result<(), Io::Error> {
-        self.bus.iter_mut().try_for_each(InPin::set_as_input)
+impl<O, B: ParallelPort, const P: usize, const BITS: usize> Interface<O, B, P, BITS> {
+    /// Switch the data bus to input, unless it already is
+    pub fn set_as_input(&mut self) -> Result<(), B::Error> {
+        if self.direction != Some(Direction::Input) {
+            self.direction = None;
+            self.bus.set_as_input()?;
+            self.direction = Some(Direction::Input);
+        }
+        Ok(())
     }
 
-    fn read_bus(&mut self) -> Result<u8, Io::Error> {
-        self.bus
-            .iter_mut()
-            .rev()
-            .try_fold(0, |out, pin| Ok(out << 1 | pin.is_high()? as u8))
+    fn read_bus(&mut self) -> Result<u8, B::Error> {
+        self.next.wait_read();
+        self.bus.read()
     }
 }
 
-// - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - -
+impl<O: OutputPin, B: OutPort<Error = O::Error>, const BITS: usize> Interface<O, B, 1, BITS> {
+    /// Latch each nibble on its own, waiting for the execution time
+    /// of the command paired with it
+    fn send_nibbles(&mut self, nibbles: &[(u8, Command)]) -> Result<(), O::Error> {
+        sleep_until(self.pins[0].end);
+        self.select_command()?;
+        self.set_as_output()?;
+        for &(nibble, command) in nibbles {
+            self.write_bus(nibble)?;
+            self.latch()?;
+            sleep(command.execution_time_at(&self.oscillator));
+        }
+        self.pins[0].end = now();
+        Ok(())
+    }
+}
 
-pub type Interface4Bit<Out, InOut, const PINS: usize> = Interface<Out, InOut, PINS, 4>;
+// - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - -
 
-impl<O, Io> Interface4Bit<O, Io, 1>
+/// Nibbles sent by [`Interface::resync()`], with the command to wait for
+///
+/// Until the first three are received the controller may be in the middle
+/// of any instruction, so they wait as long as the longest one.
+const RESYNC: [(u8, Command); 6] = [
+    (0x3, Command::Clear),
+    (0x3, Command::Clear),
+    (0x3, Command::Clear),
+    (0x2, Command::SelectBasic),
+    (0x2, Command::SelectBasic),
+    (0x0, Command::SelectBasic),
+];
+
+/// Largest value of the Address Counter, after writing the last DDRAM or
+/// CGRAM word (0x3F)
+///
+/// The AC has seven bits but none of the RAMs goes further, so a larger read
+/// can only come from a bus out of phase.
+const MAX_AC: u8 = 0x40;
+
+/// 4-bit interface with the data bus on separate pins
+pub type Interface4Bit<Out, InOut, const PINS: usize> = Interface<Out, [InOut; 4], PINS, 4>;
+
+impl<O, B> Interface<O, B, 1, 4>
 where
     O: OutputPin,
-    Io: OutPin<Error = O::Error>,
+    B: OutPort<Error = O::Error>,
 {
     pub fn write_u4(&mut self, nibble: u8) -> Result<(), O::Error> {
         self.set_as_output()?;
         self.write_bus(nibble)?;
         self.latch()
     }
+
+    /// Bring the controller back in phase with the nibbles sent
+    ///
+    /// After a spurious pulse on E the controller takes the high nibble of
+    /// each byte as the low nibble of the previous one.
+    /// As in the initialization of the datasheet, three function sets select
+    /// the 8-bit mode whatever the phase was (each nibble is a whole
+    /// instruction in 8-bit mode), and a last one goes back to 4-bit mode,
+    /// then the _Basic instruction set_ is selected with a whole byte.
+    pub fn resync(&mut self) -> Result<(), O::Error> {
+        self.send_nibbles(&RESYNC)
+    }
 }
 
-impl<O, Io> Interface4Bit<O, Io, 1>
+impl<O, B> Interface<O, B, 1, 4>
 where
     O: OutputPin,
-    Io: IoPin<Error = O::Error>,
+    B: ParallelPort<Error = O::Error>,
 {
     pub fn read_u4(&mut self) -> Result<u8, O::Error> {
         self.set_as_input()?;
@@ -124,26 +313,26 @@ where
     }
 }
 
-impl<O, Io> Output for Interface4Bit<O, Io, 1>
+impl<O, B> Output for Interface<O, B, 1, 4>
 where
     O: OutputPin,
-    Io: OutPin<Error = O::Error>,
+    B: OutPort<Error = O::Error>,
 {
     fn write_u8(&mut self, data: u8) -> Result<(), Self::Error> {
         self.write_u4(data >> 4)?;
-        sleep(10.micros()); // Enable Cycle Time, min 1800ns
+        sleep(at_least(self.timing.nibble_gap));
         self.write_u4(data & 0xF)
     }
 }
 
-impl<O, Io> Input for Interface4Bit<O, Io, 1>
+impl<O, B> Input for Interface<O, B, 1, 4>
 where
     O: OutputPin,
-    Io: IoPin<Error = O::Error>,
+    B: ParallelPort<Error = O::Error>,
 {
     fn read_u8(&mut self) -> Result<u8, Self::Error> {
         let h = self.read_u4()?;
-        sleep(10.micros()); // Enable Cycle Time, min 1800ns
+        sleep(at_least(self.timing.nibble_gap));
         let l = self.read_u4()?;
         Ok(h << 4 | l)
     }
@@ -151,12 +340,13 @@ where
 
 // - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - -
 
-pub type Interface8Bit<Out, InOut, const PINS: usize> = Interface<Out, InOut, PINS, 8>;
+/// 8-bit interface with the data bus on separate pins
+pub type Interface8Bit<Out, InOut, const PINS: usize> = Interface<Out, [InOut; 8], PINS, 8>;
 
-impl<O, Io> Output for Interface8Bit<O, Io, 1>
+impl<O, B> Output for Interface<O, B, 1, 8>
 where
     O: OutputPin,
-    Io: OutPin<Error = O::Error>,
+    B: OutPort<Error = O::Error>,
 {
     fn write_u8(&mut self, data: u8) -> Result<(), Self::Error> {
         self.set_as_output()?;
@@ -166,15 +356,15 @@ where
 
     fn write_u16(&mut self, data: u16) -> Result<(), Self::Error> {
         self.write_u8((data >> 8) as u8)?;
-        sleep(10.micros()); // Enable Cycle Time, min 1800ns
+        sleep(at_least(self.timing.nibble_gap));
         self.write_u8((data & 0xFF) as u8)
     }
 }
 
-impl<O, Io> Input for Interface8Bit<O, Io, 1>
+impl<O, B> Input for Interface<O, B, 1, 8>
 where
     O: OutputPin,
-    Io: IoPin<Error = O::Error>,
+    B: ParallelPort<Error = O::Error>,
 {
     fn read_u8(&mut self) -> Result<u8, Self::Error> {
         self.set_as_input()?;
@@ -183,7 +373,7 @@ where
 
     fn read_u16(&mut self) -> Result<u16, Self::Error> {
         let h = self.read_u8()? as u16;
-        sleep(10.micros()); // Enable Cycle Time, min 1800ns
+        sleep(at_least(self.timing.nibble_gap));
         let l = self.read_u8()? as u16;
         Ok(h << 8 | l)
     }
@@ -191,120 +381,302 @@ where
 
 // - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - -
 
-impl<O, Io, const BITS: usize> Execute for Interface<O, Io, 1, BITS>
+impl<O, B, const BITS: usize> Paced for Interface<O, B, 1, BITS> {
+    fn oscillator(&self) -> &Oscillator {
+        &self.oscillator
+    }
+
+    fn end(&mut self) -> &mut Instant {
+        &mut self.pins[0].end
+    }
+}
+
+impl<O, B, const BITS: usize> Execute for Interface<O, B, 1, BITS>
 where
     O: OutputPin,
-    Io: OutPin<Error = O::Error>,
+    B: OutPort<Error = O::Error>,
     Self: Output<Error = O::Error>,
 {
     type Error = O::Error;
 
     fn execute(&mut self, command: Command) -> Result<(), Self::Error> {
-        sleep_until(self.pins[0].end);
-
-        if let Command::Write(data) = command {
-            self.select_ram_write()?;
-            self.write_u16(data)?;
-            self.pins[0].end = now() + 72.micros();
-            return Ok(());
-        }
-
-        self.select_command()?;
-        self.write_u8(command.into_byte())?;
-        self.pins[0].end = now() + command.execution_time();
-        Ok(())
+        super::execute_paced(self, command, BITS == 8)
     }
 }
 
-impl<O, Io, const BITS: usize> ext::Execute for Interface<O, Io, 1, BITS>
+impl<O, B, const BITS: usize> ext::Execute for Interface<O, B, 1, BITS>
 where
     O: OutputPin,
-    Io: OutPin<Error = O::Error>,
+    B: OutPort<Error = O::Error>,
     Self: Output<Error = O::Error>,
 {
     fn execute_ext(&mut self, command: ext::Command) -> Result<(), Self::Error> {
-        sleep_until(self.pins[0].end);
-
-        self.select_command()?;
-        let [first, second] = command.into_bytes();
-        self.write_u8(first)?;
-        if second != 0 {
-            self.write_u8(second)?;
-        }
-        self.pins[0].end = now() + command.execution_time();
-        Ok(())
+        super::execute_ext_paced(self, command, BITS == 8)
     }
 }
 
-impl<O, Io, const BITS: usize> ExecuteRead for Interface<O, Io, 1, BITS>
+impl<O, B, const BITS: usize> ExecuteRead for Interface<O, B, 1, BITS>
 where
     O: OutputPin,
-    Io: IoPin<Error = O::Error>,
+    B: ParallelPort<Error = O::Error>,
     Self: Input<Error = O::Error>,
 {
-    type Error = Io::Error;
+    type Error = B::Error;
 
+    /// On a 4-bit bus, the controller is [resynchronized](Self::resync) when,
+    /// after the longest execution time, the busy flag is still set or the
+    /// address counter is beyond the last address of every RAM
     fn read_bf_ac(&mut self) -> Result<(bool, u8), Self::Error> {
-        sleep_until(self.pins[0].end);
+        let suspect = |(busy, ac): (bool, u8)| busy || ac > MAX_AC;
+        let read = super::read_bf_ac_paced(self)?;
+        if BITS != 4 || !suspect(read) {
+            return Ok(read);
+        }
 
-        self.select_bf_ac()?;
-        let read = self.read_u8()?;
-        Ok((read & 0b10000000 != 0, read & 0b01111111))
+        sleep(Command::Clear.execution_time_at(&self.oscillator));
+        let read = super::read_bf_ac(self)?;
+        if !suspect(read) {
+            return Ok(read);
+        }
+        log::warn!("BF/AC read {read:?}, resynchronizing the nibbles");
+        self.send_nibbles(&RESYNC)?;
+        super::read_bf_ac(self)
     }
 
     fn read(&mut self) -> Result<u16, Self::Error> {
-        sleep_until(self.pins[0].end);
-
-        self.select_ram_read()?;
-        let read = self.read_u16()?;
-        self.pins[0].end = now() + 72.micros();
-        Ok(read)
+        super::read_paced(self)
     }
 }
 
-// - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - -
+#[cfg(test)]
+mod tests {
+    use std::{format, vec, vec::Vec};
 
-impl<O, Io, const BITS: usize> Execute for &mut Interface<O, Io, 1, BITS>
-where
-    O: OutputPin,
-    Io: OutPin<Error = O::Error>,
-    Interface<O, Io, 1, BITS>: Output<Error = O::Error>,
-{
-    type Error = O::Error;
+    use crate::hal::clock;
+    use crate::mock::{Change, Event, Pin, Recorder};
+
+    use super::*;
 
-    fn init(&mut self) -> Result<(), Self::Error> {
-        Interface::init(self)
+    fn interface<const N: usize>(recorder: &Recorder) -> Interface<Pin, [Pin; N], 1, N> {
+        clock::reset();
+        let [rs, rw, e] = ["RS", "RW", "E"].map(|name| recorder.pin(name));
+        let bus = core::array::from_fn(|i| recorder.pin(&format!("DB{}", 8 - N + i)));
+        Interface::new(rs, rw, [e], bus, Timing::V5)
     }
 
-    fn execute(&mut self, command: Command) -> Result<(), Self::Error> {
-        Interface::execute(self, command)
+    /// Replay the recording, calling `step` with each event
+    /// and the levels of the signals right before it
+    fn replay(recorder: &Recorder, mut step: impl FnMut(&Event, &[bool])) {
+        let mut levels = vec![false; 8 + 3];
+        for event in recorder.events() {
+            step(&event, &levels);
+            if let Change::Level(level) = event.change {
+                levels[event.signal] = level;
+            }
+        }
     }
-}
 
-impl<O, Io, const BITS: usize> ext::Execute for &mut Interface<O, Io, 1, BITS>
-where
-    O: OutputPin,
-    Io: OutPin<Error = O::Error>,
-    Interface<O, Io, 1, BITS>: Output<Error = O::Error>,
-{
-    fn execute_ext(&mut self, command: ext::Command) -> Result<(), Self::Error> {
-        Interface::execute_ext(self, command)
+    /// RS, RW and the data bus at each falling edge of E
+    fn latched<const N: usize>(recorder: &Recorder) -> Vec<(bool, bool, u8)> {
+        let signal = |name: &str| recorder.signal(name).unwrap();
+        let [rs, rw, e] = ["RS", "RW", "E"].map(signal);
+        let db: [_; N] = core::array::from_fn(|i| signal(&format!("DB{}", 8 - N + i)));
+
+        let mut out = vec![];
+        replay(recorder, |event, levels| {
+            if event.signal == e && event.change == Change::Level(false) {
+                let data = db
+                    .iter()
+                    .rev()
+                    .fold(0, |d, &pin| d << 1 | levels[pin] as u8);
+                out.push((levels[rs], levels[rw], data));
+            }
+        });
+        out
     }
-}
 
-impl<O, Io, const BITS: usize> ExecuteRead for &mut Interface<O, Io, 1, BITS>
-where
-    O: OutputPin,
-    Io: IoPin<Error = O::Error>,
-    Interface<O, Io, 1, BITS>: Input<Error = O::Error>,
-{
-    type Error = Io::Error;
+    /// Check every edge of E against the [`Timing::V5`] parameters
+    fn check_timing(recorder: &Recorder) {
+        let timing = Timing::V5;
+        let e = recorder.signal("E").unwrap();
+        let select = ["RS", "RW"].map(|name| recorder.signal(name).unwrap());
+
+        let (mut rise, mut last_select, mut last_data) = (None, None, None);
+        replay(recorder, |event, levels| {
+            // Nothing happened before the start of the recording
+            let start = Instant::from_ticks(0);
+            let elapsed = |since: Option<Instant>| event.time - since.unwrap_or(start);
+            match event.change {
+                Change::Level(true) if event.signal == e => {
+                    assert!(elapsed(last_select) >= at_least(timing.address_setup));
+                    if rise.is_some() {
+                        assert!(elapsed(rise) >= at_least(timing.enable_cycle));
+                    }
+                    rise = Some(event.time);
+                }
+                Change::Level(false) if event.signal == e => {
+                    assert!(elapsed(rise) >= at_least(timing.enable_high));
+                    // On reads the data bus is driven by the controller
+                    if !levels[select[1]] && last_data > rise {
+                        assert!(elapsed(last_data) >= at_least(timing.data_setup));
+                    }
+                }
+                Change::Level(_) if select.contains(&event.signal) => {
+                    last_select = Some(event.time);
+                }
+                _ => last_data = Some(event.time),
+            }
+        });
+    }
 
-    fn read_bf_ac(&mut self) -> Result<(bool, u8), Self::Error> {
-        Interface::read_bf_ac(self)
+    /// Direction changes of the given pin
+    fn directions(recorder: &Recorder, name: &str) -> Vec<bool> {
+        let signal = recorder.signal(name).unwrap();
+        let events = recorder.events().into_iter().filter(|e| e.signal == signal);
+        events
+            .filter_map(|e| match e.change {
+                Change::Output(output) => Some(output),
+                Change::Level(_) => None,
+            })
+            .collect()
     }
 
-    fn read(&mut self) -> Result<u16, Self::Error> {
-        Interface::read(self)
+    #[test]
+    fn nibbles_are_sent_high_first() {
+        let recorder = Recorder::new();
+        let mut lcd = interface::<4>(&recorder);
+        lcd.ddram_addr(0x12).unwrap();
+        lcd.write(0xa5c3).unwrap();
+
+        let expected = [(false, false, 0x9), (false, false, 0x2)];
+        let data = [0xa, 0x5, 0xc, 0x3].map(|nibble| (true, false, nibble));
+        assert_eq!(latched::<4>(&recorder), [&expected[..], &data].concat());
+        check_timing(&recorder);
+    }
+
+    #[test]
+    fn bytes_are_sent_high_first() {
+        let recorder = Recorder::new();
+        let mut lcd = interface::<8>(&recorder);
+        lcd.ddram_addr(0x12).unwrap();
+        lcd.write(0xa5c3).unwrap();
+
+        let expected = [
+            (false, false, 0x92),
+            (true, false, 0xa5),
+            (true, false, 0xc3),
+        ];
+        assert_eq!(latched::<8>(&recorder), expected);
+        check_timing(&recorder);
+    }
+
+    #[test]
+    fn reads_respect_the_timing() {
+        let recorder = Recorder::new();
+        let mut lcd = interface::<8>(&recorder);
+        lcd.ddram_addr(0x12).unwrap();
+        for (i, level) in [false, true, false, false, true, false, false, false]
+            .into_iter()
+            .enumerate()
+        {
+            recorder.drive(&format!("DB{i}"), [level]);
+        }
+        assert_eq!(lcd.read_bf_ac().unwrap(), (false, 0x12));
+
+        // The data is sampled after tDDR from the rising edge of E,
+        // DB7 is the only line that changes from the command written before
+        let [e, db7] = ["E", "DB7"].map(|name| recorder.transitions(name));
+        let read = db7.last().unwrap().0 - e[e.len() - 2].0;
+        assert!(read >= at_least(Timing::V5.data_delay));
+        check_timing(&recorder);
+    }
+
+    #[test]
+    fn impossible_address_resyncs_the_nibbles() {
+        let recorder = Recorder::new();
+        let mut lcd = interface::<4>(&recorder);
+        // Two reads with AC beyond the last address, then a valid one
+        let nibbles = [0x4, 0x5, 0x4, 0x5, 0x1, 0x2];
+        for bit in 0..4 {
+            let levels = nibbles.map(|nibble| nibble & 1 << bit != 0);
+            recorder.drive(&format!("DB{}", 4 + bit), levels);
+        }
+        assert_eq!(lcd.read_bf_ac().unwrap(), (false, 0x12));
+
+        let read = [(false, true, 0x4), (false, true, 0x5)];
+        let resync = [0x3, 0x3, 0x3, 0x2, 0x2, 0x0].map(|nibble| (false, false, nibble));
+        let last = [(false, true, 0x1), (false, true, 0x2)];
+        let expected = [&read[..], &read, &resync, &last].concat();
+        assert_eq!(latched::<4>(&recorder), expected);
+
+        // The first three nibbles of the resync wait as long as a clear
+        let e = recorder.transitions("E");
+        for nibble in 4..7 {
+            let wait = e[2 * nibble + 2].0 - e[2 * nibble + 1].0;
+            assert!(wait >= Command::Clear.execution_time());
+        }
+        check_timing(&recorder);
+    }
+
+    #[test]
+    fn address_after_the_last_word_is_valid() {
+        let recorder = Recorder::new();
+        let mut lcd = interface::<4>(&recorder);
+        let nibbles = [0x4, 0x0];
+        for bit in 0..4 {
+            let levels = nibbles.map(|nibble| nibble & 1 << bit != 0);
+            recorder.drive(&format!("DB{}", 4 + bit), levels);
+        }
+        assert_eq!(lcd.read_bf_ac().unwrap(), (false, 0x40));
+        assert_eq!(
+            latched::<4>(&recorder),
+            [(false, true, 0x4), (false, true, 0x0)]
+        );
+    }
+
+    #[test]
+    fn bus_direction_is_only_switched_when_needed() {
+        let recorder = Recorder::new();
+        let mut lcd = interface::<8>(&recorder);
+        lcd.ddram_addr(0).unwrap();
+        lcd.write(0x4142).unwrap();
+        lcd.read_bf_ac().unwrap();
+        lcd.read_bf_ac().unwrap();
+        lcd.write(0x4344).unwrap();
+
+        for bit in 0..8 {
+            assert_eq!(directions(&recorder, &format!("DB{bit}")), [false, true]);
+        }
+        // The bus is never driven by both ends: it switches to input
+        // after the last write has been latched, and back before the next one
+        let switches: Vec<_> = recorder
+            .events()
+            .into_iter()
+            .filter(|e| matches!(e.change, Change::Output(_)))
+            .collect();
+        let e = recorder.transitions("E");
+        let [to_input, to_output] = [switches[0].time, switches[8].time];
+        let falls: Vec<_> = e
+            .iter()
+            .filter(|(_, level)| !level)
+            .map(|(t, _)| *t)
+            .collect();
+        assert!(falls[2] <= to_input && to_output >= falls[4]);
+    }
+
+    #[test]
+    fn shared_bus_keeps_the_deadlines() {
+        clock::reset();
+        let recorder = Recorder::new();
+        let [rs, rw, e0, e1] = ["RS", "RW", "E0", "E1"].map(|name| recorder.pin(name));
+        let bus: [Pin; 8] = core::array::from_fn(|i| recorder.pin(&format!("DB{i}")));
+        let mut lcd = Interface::<_, _, 2, 8>::new(rs, rw, [e0, e1], bus, Timing::V5);
+        lcd.get(0).unwrap().clear().unwrap();
+        lcd.get(1).unwrap().clear().unwrap();
+        lcd.get(0).unwrap().home().unwrap();
+
+        let [e0, e1] = ["E0", "E1"].map(|name| recorder.transitions(name));
+        assert!(e1[0].0 - e0[0].0 >= at_least(Timing::V5.enable_cycle));
+        assert!(e0[2].0 - e0[1].0 >= Command::Clear.execution_time());
     }
 }
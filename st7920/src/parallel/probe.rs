@@ -0,0 +1,161 @@
+//! Display presence and bus width detection
+//!
+//! [`run()`] looks for a display on a parallel bus with all of DB0-7
+//! connected, by setting the DDRAM address and reading it back from the
+//! Address Counter: first through an 8-bit interface, then through a 4-bit
+//! one on DB4-7 after [resynchronizing](Interface4Bit::resync) the controller.
+//! The interface whose reads follow the addresses is returned, ready to be
+//! [initialized](crate::Execute::init).
+//!
+//! When neither does, the reads tell a missing display apart from one that
+//! doesn't answer properly: a bus nobody drives always reads the same value
+//! (pulled up or down), or the last command written when it holds its charge.
+//!
+//! The reads don't go through [`ExecuteRead`], so an Address Counter out of
+//! range doesn't [resynchronize](Interface4Bit::resync) the 4-bit interface
+//! again: the probe expects wrong reads, and checks them by itself.
+
+use embedded_hal::digital::OutputPin;
+use fugit::ExtU32;
+
+use crate::hal::{sleep, IoPin};
+use crate::{Command, Execute};
+
+use super::timing::Timing;
+use super::{Input, Interface4Bit, Interface8Bit, Paced};
+
+/// DDRAM addresses set and read back, each bit is high in one of them
+const ADDRESSES: [u8; 2] = [0b10101, 0b01010];
+
+pub enum Probe<O, Io> {
+    /// Nothing drives the data bus
+    Absent(Interface8Bit<O, Io, 1>),
+    /// The data bus is driven, but the Address Counter
+    /// doesn't follow the addresses set
+    Unresponsive(Interface8Bit<O, Io, 1>),
+    /// The display answers on DB4-7, the pins of DB0-3 are given back
+    FourBit(Interface4Bit<O, Io, 1>, [Io; 4]),
+    EightBit(Interface8Bit<O, Io, 1>),
+}
+
+/// Set each of the [`ADDRESSES`] and read the busy flag and Address Counter back
+fn read_back<Lcd, E>(lcd: &mut Lcd) -> Result<[u8; 2], E>
+where
+    Lcd: Execute<Error = E> + Input<Error = E> + Paced,
+{
+    let mut reads = [0; 2];
+    for (read, addr) in reads.iter_mut().zip(ADDRESSES) {
+        lcd.ddram_addr(addr)?;
+        let (bf, ac) = super::read_bf_ac_paced(lcd)?;
+        *read = (bf as u8) << 7 | ac;
+    }
+    Ok(reads)
+}
+
+/// Look for a display and detect the width of its bus,
+/// see the [module](self) documentation
+pub fn run<O, Io>(
+    rs: O,
+    rw: O,
+    e: O,
+    bus: [Io; 8],
+    timing: Timing,
+) -> Result<Probe<O, Io>, O::Error>
+where
+    O: OutputPin,
+    Io: IoPin<Error = O::Error>,
+{
+    // Give the controller time to power up
+    sleep(40.millis());
+
+    let mut lcd = Interface8Bit::new(rs, rw, [e], bus, timing);
+    // In 4-bit mode only DB4-7 are latched, and the last two function sets
+    // make up an 8-bit one whether the first completes a pending nibble or not
+    for _ in 0..3 {
+        lcd.select_basic()?;
+    }
+    let eight = read_back(&mut lcd)?;
+    if eight == ADDRESSES {
+        return Ok(Probe::EightBit(lcd));
+    }
+
+    let (rs, rw, [e], [d0, d1, d2, d3, d4, d5, d6, d7]) = lcd.release();
+    let mut lcd = Interface4Bit::new(rs, rw, [e], [d4, d5, d6, d7], timing);
+    lcd.resync()?;
+    let four = read_back(&mut lcd)?;
+    if four == ADDRESSES {
+        return Ok(Probe::FourBit(lcd, [d0, d1, d2, d3]));
+    }
+
+    let (rs, rw, [e], [d4, d5, d6, d7]) = lcd.release();
+    let bus = [d0, d1, d2, d3, d4, d5, d6, d7];
+    let lcd = Interface8Bit::new(rs, rw, [e], bus, timing);
+
+    // On a 4-bit bus the controller may have been resynchronized
+    // before reading, so only the 8-bit reads are checked for the commands
+    let floating = eight.iter().chain(&four).all(|read| *read == eight[0]);
+    let holding = eight == ADDRESSES.map(|addr| Command::DdRamAddr(addr).into_byte());
+    Ok(match floating || holding {
+        true => Probe::Absent(lcd),
+        false => Probe::Unresponsive(lcd),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use core::ops::Range;
+    use std::format;
+
+    use crate::hal::clock;
+    use crate::mock::{Pin, Recorder};
+
+    use super::*;
+
+    /// Probe the bus, with the values queued by `answer` on its lines
+    fn probe(answer: impl FnOnce(&Recorder)) -> (Recorder, Probe<Pin, Pin>) {
+        clock::reset();
+        let recorder = Recorder::new();
+        let [rs, rw, e] = ["RS", "RW", "E"].map(|name| recorder.pin(name));
+        let bus = core::array::from_fn(|i| recorder.pin(&format!("DB{i}")));
+        answer(&recorder);
+        let probe = run(rs, rw, e, bus, Timing::V5).unwrap();
+        (recorder, probe)
+    }
+
+    /// Queue the values read from the given data lines
+    fn drive(recorder: &Recorder, lines: Range<usize>, values: &[u8]) {
+        for (bit, line) in lines.enumerate() {
+            let levels = values.iter().map(|value| value & 1 << bit != 0);
+            recorder.drive(&format!("DB{line}"), levels);
+        }
+    }
+
+    #[test]
+    fn eight_bit_display_is_found() {
+        let (_, probe) = probe(|recorder| drive(recorder, 0..8, &ADDRESSES));
+        assert!(matches!(probe, Probe::EightBit(_)));
+    }
+
+    #[test]
+    fn four_bit_display_is_found() {
+        // Only DB4-7 are driven, the 8-bit reads don't follow the addresses
+        let (_, probe) = probe(|recorder| {
+            drive(recorder, 0..8, &[0x10, 0x00]);
+            drive(recorder, 4..8, &[0x1, 0x5, 0x0, 0xA]);
+        });
+        assert!(matches!(probe, Probe::FourBit(..)));
+    }
+
+    #[test]
+    fn missing_display_is_found_without_resyncing() {
+        // The bus holds the last command written
+        let (recorder, probe) = probe(|_| ());
+        assert!(matches!(probe, Probe::Absent(_)));
+
+        // Three function sets and two address reads on the 8-bit bus,
+        // then a single resync and two address reads on the 4-bit one
+        let e = recorder.transitions("E");
+        let latches = e.iter().filter(|(_, level)| !level).count();
+        assert_eq!(latches, 3 + 2 * 2 + 6 + 2 * 4);
+    }
+}
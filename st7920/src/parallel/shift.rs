@@ -0,0 +1,176 @@
+//! 8-bit bus through a 74HC595 shift register
+//!
+//! The data bus is shifted into the register over an [`SpiBus`] (a
+//! [`BitBang`](crate::serial::bitbang::BitBang) one works too), in mode 0
+//! with the most significant bit first, and copied to the outputs with a
+//! pulse on RCLK. QA to QH drive DB0 to DB7, while RS and E are driven
+//! directly.
+//!
+//! RW is expected to be tied low, so the bus is write-only and the
+//! execution time of each command is always waited for.
+
+use either::Either::{self, Left, Right};
+use embedded_hal::{digital::OutputPin, spi::SpiBus};
+
+use crate::hal::{at_least, now, sleep, Instant};
+use crate::{ext, Command, Execute, Oscillator};
+
+use super::timing::{Deadlines, Timing};
+use super::{Control, Output, Paced};
+
+pub struct ShiftRegister<Spi, O> {
+    spi: Spi,
+    rclk: O,
+    rs: O,
+    e: O,
+    timing: Timing,
+    oscillator: Oscillator,
+    end: Instant,
+    next: Deadlines,
+}
+
+impl<Spi, O> ShiftRegister<Spi, O> {
+    pub fn new(spi: Spi, rclk: O, rs: O, e: O, timing: Timing) -> Self {
+        Self {
+            spi,
+            rclk,
+            rs,
+            e,
+            timing,
+            oscillator: Oscillator::default(),
+            end: now(),
+            next: Deadlines::new(),
+        }
+    }
+
+    /// Compute the execution times for the given oscillator
+    /// instead of the [nominal](Oscillator::NOMINAL) one
+    pub fn with_oscillator(mut self, oscillator: Oscillator) -> Self {
+        self.oscillator = oscillator;
+        self
+    }
+
+    pub fn timing(&self) -> &Timing {
+        &self.timing
+    }
+
+    pub fn release(self) -> (Spi, O, O, O) {
+        (self.spi, self.rclk, self.rs, self.e)
+    }
+}
+
+// - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - -
+
+impl<Spi: SpiBus, O: OutputPin> Control for ShiftRegister<Spi, O> {
+    type Error = Either<Spi::Error, O::Error>;
+
+    fn enable(&mut self) -> Result<(), Self::Error> {
+        self.next.wait_rise();
+        self.e.set_high().map_err(Right)?;
+        self.next.rose(&self.timing);
+        Ok(())
+    }
+
+    fn disable(&mut self) -> Result<(), Self::Error> {
+        self.next.wait_fall();
+        self.e.set_low().map_err(Right)
+    }
+
+    /// Select the register with RS, RW is tied low so `rw` is ignored
+    fn select(&mut self, rs: bool, _rw: bool) -> Result<(), Self::Error> {
+        self.rs.set_state(rs.into()).map_err(Right)?;
+        self.next.selected(&self.timing);
+        Ok(())
+    }
+}
+
+impl<Spi: SpiBus, O: OutputPin> Output for ShiftRegister<Spi, O> {
+    fn write_u8(&mut self, data: u8) -> Result<(), Self::Error> {
+        self.spi.write(&[data]).map_err(Left)?;
+        self.spi.flush().map_err(Left)?;
+        self.rclk.set_high().map_err(Right)?;
+        self.rclk.set_low().map_err(Right)?;
+        self.next.written(&self.timing);
+        self.latch()
+    }
+
+    fn write_u16(&mut self, data: u16) -> Result<(), Self::Error> {
+        self.write_u8((data >> 8) as u8)?;
+        sleep(at_least(self.timing.nibble_gap));
+        self.write_u8((data & 0xFF) as u8)
+    }
+}
+
+// - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - -
+
+impl<Spi, O> Paced for ShiftRegister<Spi, O> {
+    fn oscillator(&self) -> &Oscillator {
+        &self.oscillator
+    }
+
+    fn end(&mut self) -> &mut Instant {
+        &mut self.end
+    }
+}
+
+impl<Spi: SpiBus, O: OutputPin> Execute for ShiftRegister<Spi, O> {
+    type Error = Either<Spi::Error, O::Error>;
+
+    fn execute(&mut self, command: Command) -> Result<(), Self::Error> {
+        super::execute_paced(self, command, true)
+    }
+}
+
+impl<Spi: SpiBus, O: OutputPin> ext::Execute for ShiftRegister<Spi, O> {
+    fn execute_ext(&mut self, command: ext::Command) -> Result<(), Self::Error> {
+        super::execute_ext_paced(self, command, true)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::vec::Vec;
+
+    use crate::hal::{clock, Duration};
+    use crate::mock::{Pin, Recorder, Spi};
+
+    use super::*;
+
+    fn shift_register(recorder: &Recorder) -> ShiftRegister<Spi, Pin> {
+        clock::reset();
+        let spi = recorder.spi("SRCLK", "SER", Duration::from_ticks(2));
+        let [rclk, rs, e] = ["RCLK", "RS", "E"].map(|name| recorder.pin(name));
+        ShiftRegister::new(spi, rclk, rs, e, Timing::V5)
+    }
+
+    #[test]
+    fn bytes_are_latched_after_the_setup_times() {
+        let recorder = Recorder::new();
+        let mut lcd = shift_register(&recorder);
+        lcd.ddram_addr(0x12).unwrap();
+        lcd.write(0xa5c3).unwrap();
+        assert_eq!(recorder.shifted("SRCLK", "SER"), [0x92, 0xa5, 0xc3]);
+
+        let timing = Timing::V5;
+        let rises = |name| recorder.transitions(name).into_iter().filter(|t| t.1);
+        let stored: Vec<_> = rises("RCLK").map(|(time, _)| time).collect();
+        let e = recorder.transitions("E");
+        let rs = recorder.transitions("RS");
+        assert_eq!(e.len(), 2 * stored.len());
+
+        for (pulse, &stored) in e.chunks_exact(2).zip(&stored) {
+            let [(rise, true), (fall, false)] = pulse else {
+                panic!("E pulses out of order: {pulse:?}");
+            };
+            let selected = rs.iter().rfind(|t| t.0 <= *rise);
+            let selected = selected.map_or(Instant::from_ticks(0), |t| t.0);
+            assert!(*rise - selected >= at_least(timing.address_setup));
+            assert!(*fall - *rise >= at_least(timing.enable_high));
+            assert!(*fall - stored >= at_least(timing.data_setup));
+        }
+        let enabled: Vec<_> = rises("E").map(|(time, _)| time).collect();
+        for pair in enabled.windows(2) {
+            assert!(pair[1] - pair[0] >= at_least(timing.enable_cycle));
+        }
+    }
+}
@@ -0,0 +1,158 @@
+//! Timing characteristics of the parallel bus
+//!
+//! ```text
+//!            ___________________________________________
+//! RS, RW  __X___________________________________________X___
+//!           |<- tAS ->|       PWEH        |
+//!                     |___________________|             ____
+//! E       ____________|                   |____________|
+//!                     |<-------------- tC ------------>|
+//!                          |<-- tDSW -->|
+//!                     ______________________________
+//! DB0-7   -----------X______________________________X--------
+//!                     |<- tDDR ->|  (valid on reads)
+//! ```
+
+use fugit::NanosDurationU32 as Nanos;
+
+use crate::hal::{at_least, now, sleep_until, Instant};
+
+const fn ns(ticks: u32) -> Nanos {
+    Nanos::from_ticks(ticks)
+}
+
+/// Minimum times to respect on the parallel bus
+///
+/// Times are rounded up as explained in [`hal::at_least()`](crate::hal::at_least):
+/// with the microsecond resolution of the clock, every non-zero time below
+/// one microsecond (like all the presets except the enable cycle) ends up
+/// waiting two microseconds.
+/// A time can be set to zero when the pin operations are already slow
+/// enough to respect it, so that no time is spent waiting for it.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Timing {
+    /// Address setup time (tAS): RS and RW stable before E rises
+    pub address_setup: Nanos,
+    /// Enable pulse width (PWEH): how long E stays high
+    pub enable_high: Nanos,
+    /// Enable cycle time (tC): from one rising edge of E to the next one
+    pub enable_cycle: Nanos,
+    /// Data setup time (tDSW): data stable before E falls when writing
+    pub data_setup: Nanos,
+    /// Data delay time (tDDR): data valid after E rises when reading
+    pub data_delay: Nanos,
+    /// Additional time to wait between the two nibbles, or bytes, of a transfer
+    ///
+    /// The datasheet doesn't require anything between them other than the
+    /// enable cycle time, which is already respected, so the presets set it to zero.
+    pub nibble_gap: Nanos,
+}
+
+/// A [`Timing`] parameter that is shorter than required
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Parameter {
+    AddressSetup,
+    EnableHigh,
+    EnableCycle,
+    DataSetup,
+    DataDelay,
+}
+
+impl Timing {
+    /// Datasheet minimum values with VDD between 4.5V and 5.5V
+    pub const V5: Self = Self {
+        address_setup: ns(10),
+        enable_high: ns(140),
+        enable_cycle: ns(1200),
+        data_setup: ns(40),
+        data_delay: ns(100),
+        nibble_gap: ns(0),
+    };
+
+    /// Datasheet minimum values with VDD between 2.7V and 4.5V
+    pub const V3_3: Self = Self {
+        address_setup: ns(10),
+        enable_high: ns(160),
+        enable_cycle: ns(1800),
+        data_setup: ns(40),
+        data_delay: ns(260),
+        nibble_gap: ns(0),
+    };
+
+    /// Check that every parameter is at least as long as the `required` one
+    /// (usually one of the presets), and that the enable cycle
+    /// is long enough to contain the enable pulse
+    pub fn validate(&self, required: &Self) -> Result<(), Parameter> {
+        use Parameter::*;
+        let checks = [
+            (self.address_setup, required.address_setup, AddressSetup),
+            (self.enable_high, required.enable_high, EnableHigh),
+            (self.enable_cycle, required.enable_cycle, EnableCycle),
+            (self.enable_cycle, self.enable_high, EnableCycle),
+            (self.data_setup, required.data_setup, DataSetup),
+            (self.data_delay, required.data_delay, DataDelay),
+        ];
+        match checks.into_iter().find(|(value, min, _)| value < min) {
+            Some((.., parameter)) => Err(parameter),
+            None => Ok(()),
+        }
+    }
+}
+
+/// Earliest instants at which each step of a latch can happen,
+/// according to the [`Timing`]
+#[derive(Clone, Copy)]
+pub(crate) struct Deadlines {
+    /// When the enable signal can rise (tAS and tC)
+    rise: Instant,
+    /// When the enable signal can fall (PWEH and tDSW)
+    fall: Instant,
+    /// When the data bus can be read (tDDR)
+    read: Instant,
+}
+
+impl Deadlines {
+    pub(crate) fn new() -> Self {
+        let now = now();
+        Self {
+            rise: now,
+            fall: now,
+            read: now,
+        }
+    }
+
+    /// Wait until the enable signal can rise
+    pub(crate) fn wait_rise(&self) {
+        sleep_until(self.rise)
+    }
+
+    /// Wait until the enable signal can fall
+    pub(crate) fn wait_fall(&self) {
+        sleep_until(self.fall)
+    }
+
+    /// Wait until the data bus can be read
+    pub(crate) fn wait_read(&self) {
+        sleep_until(self.read)
+    }
+
+    /// The enable signal has just risen
+    pub(crate) fn rose(&mut self, timing: &Timing) {
+        let now = now();
+        self.rise = now + at_least(timing.enable_cycle);
+        self.fall = self.fall.max(now + at_least(timing.enable_high));
+        self.read = now + at_least(timing.data_delay);
+    }
+
+    /// RS and RW have just been set
+    pub(crate) fn selected(&mut self, timing: &Timing) {
+        let setup = now() + at_least(timing.address_setup);
+        self.rise = self.rise.max(setup);
+    }
+
+    /// The data bus has just been written
+    pub(crate) fn written(&mut self, timing: &Timing) {
+        let setup = now() + at_least(timing.data_setup);
+        self.fall = self.fall.max(setup);
+    }
+}
@@ -0,0 +1,180 @@
+//! Drawing on the GDRAM without a copy of the screen
+//!
+//! [`Pixels`] draws on a 128x64 display by reading the words it touches,
+//! changing them and writing them back: the words of each row of the shape
+//! are read and written together, after selecting the address of the first
+//! one. A row is only read when the fill inverts the pixels or doesn't
+//! cover its first or last word as a whole, in which case all of its words
+//! are read, to set the address only once.
+//!
+//! The _Extended instruction set_ must be selected, the GDRAM is accessed
+//! whether the graphic display is on or not. Pixels outside of the screen
+//! are ignored.
+
+use core::ops::Range;
+
+use crate::framebuffer::{self, HEIGHT, WIDTH, WORDS};
+use crate::{ext, ExecuteRead};
+
+/// What to do with the pixels of a shape
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Fill {
+    On,
+    Off,
+    Invert,
+}
+
+impl Fill {
+    fn apply(self, word: u16, mask: u16) -> u16 {
+        match self {
+            Fill::On => word | mask,
+            Fill::Off => word & !mask,
+            Fill::Invert => word ^ mask,
+        }
+    }
+}
+
+/// Bits of the given word covered by the pixels
+fn mask(word: usize, pixels: &Range<usize>) -> u16 {
+    let start = word * 16;
+    let lo = pixels.start.max(start) - start;
+    let hi = pixels.end.min(start + 16) - start;
+    (0xFFFF_u32 >> lo & !(0xFFFF_u32 >> hi)) as u16
+}
+
+pub struct Pixels<T> {
+    lcd: T,
+}
+
+impl<T> Pixels<T> {
+    pub fn new(lcd: T) -> Self {
+        Self { lcd }
+    }
+
+    pub(crate) fn lcd(&mut self) -> &mut T {
+        &mut self.lcd
+    }
+
+    pub fn release(self) -> T {
+        self.lcd
+    }
+}
+
+impl<T, E> Pixels<T>
+where
+    T: ext::Execute<Error = E> + ExecuteRead<Error = E>,
+{
+    fn select(&mut self, word: usize, row: usize) -> Result<(), E> {
+        let (x, y) = framebuffer::address(word as u8, row as u8);
+        self.lcd.graphic_ram_addr(x, y)
+    }
+
+    /// Apply the fill to the given pixels of a row of the screen
+    fn modify_row(&mut self, row: usize, pixels: Range<usize>, fill: Fill) -> Result<(), E> {
+        let words = pixels.start / 16..pixels.end.div_ceil(16);
+        let mut buf = [0; WORDS];
+        let buf = &mut buf[..words.len()];
+
+        let masks = words.clone().map(|word| mask(word, &pixels));
+        if fill == Fill::Invert || masks.clone().any(|mask| mask != 0xFFFF) {
+            self.select(words.start, row)?;
+            // The first read after setting the address returns stale data
+            self.lcd.read()?;
+            for word in buf.iter_mut() {
+                *word = self.lcd.read()?;
+            }
+        }
+        for (word, mask) in buf.iter_mut().zip(masks) {
+            *word = fill.apply(*word, mask);
+        }
+
+        self.select(words.start, row)?;
+        self.lcd.write_burst(buf)
+    }
+
+    /// Apply the fill to a rectangle, `x` and `y` being its top left corner
+    pub fn fill_rect(
+        &mut self,
+        x: usize,
+        y: usize,
+        width: usize,
+        height: usize,
+        fill: Fill,
+    ) -> Result<(), E> {
+        let columns = x.min(WIDTH)..x.saturating_add(width).min(WIDTH);
+        if columns.is_empty() {
+            return Ok(());
+        }
+        for row in y.min(HEIGHT)..y.saturating_add(height).min(HEIGHT) {
+            self.modify_row(row, columns.clone(), fill)?;
+        }
+        Ok(())
+    }
+
+    pub fn set_pixel(&mut self, x: usize, y: usize, on: bool) -> Result<(), E> {
+        let fill = match on {
+            true => Fill::On,
+            false => Fill::Off,
+        };
+        self.fill_rect(x, y, 1, 1, fill)
+    }
+
+    pub fn xor_pixel(&mut self, x: usize, y: usize) -> Result<(), E> {
+        self.fill_rect(x, y, 1, 1, Fill::Invert)
+    }
+
+    /// Horizontal line going right from `(x, y)`
+    pub fn hline(&mut self, x: usize, y: usize, width: usize, fill: Fill) -> Result<(), E> {
+        self.fill_rect(x, y, width, 1, fill)
+    }
+
+    /// Vertical line going down from `(x, y)`
+    pub fn vline(&mut self, x: usize, y: usize, height: usize, fill: Fill) -> Result<(), E> {
+        self.fill_rect(x, y, 1, height, fill)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::ext::Execute as _;
+    use crate::mock::Emulator;
+
+    use super::*;
+
+    /// An emulator with every GDRAM word set to the same pattern
+    fn pixels() -> Pixels<Emulator> {
+        let mut lcd = Emulator::new();
+        lcd.select_extended().unwrap();
+        lcd.gdram = [[0xA5A5; 16]; 64];
+        Pixels::new(lcd)
+    }
+
+    #[test]
+    fn edges_of_the_rectangle_keep_the_other_pixels() {
+        let mut pixels = pixels();
+        // Across three words and the two halves of the screen
+        pixels.fill_rect(8, 30, 32, 4, Fill::On).unwrap();
+
+        let gdram = pixels.release().gdram;
+        let filled = [0xA5FF, 0xFFFF, 0xFFA5, 0xA5A5];
+        for (y, x) in [(30, 0), (31, 0), (0, 8), (1, 8)] {
+            assert_eq!(gdram[y][x..x + 4], filled, "row {y}, word {x}");
+        }
+        for (y, x) in [(29, 0), (2, 8)] {
+            assert_eq!(gdram[y][x..x + 4], [0xA5A5; 4], "row {y}, word {x}");
+        }
+    }
+
+    #[test]
+    fn rectangles_ending_mid_word_are_read_first() {
+        let mut pixels = pixels();
+        pixels.fill_rect(0, 0, 20, 1, Fill::Off).unwrap();
+        pixels.fill_rect(16, 1, 32, 1, Fill::Invert).unwrap();
+        pixels.set_pixel(127, 63, true).unwrap();
+
+        let gdram = pixels.release().gdram;
+        assert_eq!(gdram[0][..3], [0x0000, 0x05A5, 0xA5A5]);
+        assert_eq!(gdram[1][..4], [0xA5A5, 0x5A5A, 0x5A5A, 0xA5A5]);
+        assert_eq!(gdram[31][14..], [0xA5A5, 0xA5A5 | 1]);
+    }
+}
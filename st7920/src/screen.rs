@@ -0,0 +1,198 @@
+//! Text and graphics layers of a 128x64 display
+//!
+//! The controller shows the characters of the DDRAM over the pixels of the
+//! GDRAM, combining them with an exclusive or (see [`Mode::pixel()`]).
+//! [`Screen`] keeps a copy of the text, so that it can be hidden when only
+//! the graphics should be shown, and selects the right instruction set
+//! before writing to either layer.
+
+use crate::capture::Text;
+use crate::ext;
+use crate::framebuffer::{Framebuffer, HEIGHT, WORDS};
+
+/// Which layers are shown
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Mode {
+    /// Both layers, the pixels of the characters invert the graphics
+    Overlay,
+    /// Only the text, the graphic display is turned off
+    TextOnly,
+    /// Only the graphics, the DDRAM is filled with spaces
+    /// and the text is written back when it's shown again
+    GraphicsOnly,
+}
+
+impl Mode {
+    /// Whether a pixel on the screen is on, given the pixel
+    /// of the character and the pixel of the GDRAM at its place
+    pub fn pixel(self, text: bool, graphic: bool) -> bool {
+        match self {
+            Mode::Overlay => text ^ graphic,
+            Mode::TextOnly => text,
+            Mode::GraphicsOnly => graphic,
+        }
+    }
+
+    fn graphics(self) -> bool {
+        self != Mode::TextOnly
+    }
+}
+
+pub struct Screen<T> {
+    lcd: T,
+    mode: Mode,
+    /// Text written, shown unless in [`Mode::GraphicsOnly`]
+    text: Text,
+    /// Whether the _Extended instruction set_ is selected
+    extended: bool,
+}
+
+impl<T> Screen<T> {
+    /// Wrap an [initialized](crate::Execute::init) display, with the text
+    /// cleared, the graphic display off and the _Basic instruction set_ selected
+    pub fn new(lcd: T) -> Self {
+        Self {
+            lcd,
+            mode: Mode::TextOnly,
+            text: Text::BLANK,
+            extended: false,
+        }
+    }
+
+    pub fn mode(&self) -> Mode {
+        self.mode
+    }
+
+    pub fn text(&self) -> &Text {
+        &self.text
+    }
+
+    pub fn release(self) -> T {
+        self.lcd
+    }
+}
+
+impl<T: ext::Execute> Screen<T> {
+    fn select_basic(&mut self) -> Result<(), T::Error> {
+        if self.extended {
+            self.lcd.select_basic()?;
+            self.extended = false;
+        }
+        Ok(())
+    }
+
+    /// Select the _Extended instruction set_, turning the graphic display
+    /// on or off according to the mode
+    fn select_extended(&mut self) -> Result<(), T::Error> {
+        self.lcd.select_extended()?;
+        if self.mode.graphics() {
+            self.lcd.select_graphic()?;
+        }
+        self.extended = true;
+        Ok(())
+    }
+
+    fn load_text(&mut self, text: &Text) -> Result<(), T::Error> {
+        self.select_basic()?;
+        for (words, addr) in text.lines.iter().zip(Text::ADDRESSES) {
+            self.lcd.ddram_addr(addr)?;
+            self.lcd.write_burst(words)?;
+        }
+        Ok(())
+    }
+
+    pub fn set_mode(&mut self, mode: Mode) -> Result<(), T::Error> {
+        let hide = self.mode != Mode::GraphicsOnly && mode == Mode::GraphicsOnly;
+        let show = self.mode == Mode::GraphicsOnly && mode != Mode::GraphicsOnly;
+        self.mode = mode;
+        self.select_extended()?;
+        if hide {
+            self.load_text(&Text::BLANK)?;
+        }
+        if show {
+            let text = self.text;
+            self.load_text(&text)?;
+        }
+        Ok(())
+    }
+
+    /// Write half-width characters (or the two bytes of full-width ones,
+    /// starting from an even column) from the given column of a line
+    ///
+    /// The characters past the end of the line are dropped,
+    /// as is the whole text on a line past the last one.
+    pub fn write_text(&mut self, line: usize, column: usize, text: &[u8]) -> Result<(), T::Error> {
+        let end = column.saturating_add(text.len()).min(2 * WORDS);
+        let Some(start) = Text::address(line) else {
+            return Ok(());
+        };
+        if column >= end {
+            return Ok(());
+        }
+        let words = &mut self.text.lines[line];
+        for (i, &byte) in (column..end).zip(text) {
+            let word = &mut words[i / 2];
+            *word = match i % 2 {
+                0 => *word & 0x00FF | (byte as u16) << 8,
+                _ => *word & 0xFF00 | byte as u16,
+            };
+        }
+        if self.mode == Mode::GraphicsOnly {
+            return Ok(());
+        }
+
+        let words = column / 2..end.div_ceil(2);
+        let addr = start + words.start as u8;
+        let data = self.text.lines[line];
+        self.select_basic()?;
+        self.lcd.ddram_addr(addr)?;
+        self.lcd.write_burst(&data[words])
+    }
+
+    pub fn clear_text(&mut self) -> Result<(), T::Error> {
+        self.text = Text::BLANK;
+        match self.mode {
+            Mode::GraphicsOnly => Ok(()),
+            _ => self.load_text(&Text::BLANK),
+        }
+    }
+
+    /// Write the whole graphics layer, even when it's not shown
+    pub fn draw(&mut self, fb: &Framebuffer) -> Result<(), T::Error> {
+        if !self.extended {
+            self.select_extended()?;
+        }
+        for row in 0..HEIGHT / 2 {
+            self.lcd.graphic_ram_addr(0, row as u8)?;
+            // The Address Counter moves on to the bottom half of the screen
+            self.lcd.write_burst(fb.row(row))?;
+            self.lcd.write_burst(fb.row(row + HEIGHT / 2))?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::mock::Emulator;
+
+    use super::*;
+
+    #[test]
+    fn text_out_of_the_screen_is_dropped() {
+        let mut screen = Screen::new(Emulator::new());
+        screen.write_text(1, 14, b"abcd").unwrap();
+        screen.write_text(4, 0, b"lost").unwrap();
+        screen.write_text(usize::MAX, 0, b"lost").unwrap();
+
+        let mut expected = Text::BLANK;
+        expected.lines[1][7] = u16::from_be_bytes(*b"ab");
+        assert_eq!(*screen.text(), expected);
+        let lcd = screen.release();
+        assert_eq!(
+            lcd.ddram[Text::ADDRESSES[1] as usize + 7],
+            expected.lines[1][7]
+        );
+        assert_eq!(lcd.ddram.iter().filter(|&&word| word != 0x2020).count(), 1);
+    }
+}
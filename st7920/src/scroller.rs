@@ -0,0 +1,143 @@
+//! Vertical scrolling through a list of text lines
+//!
+//! The DDRAM holds 4 rows of 16 pixels high characters, one every `0x10`
+//! words, which [`ScrollOffset`](ext::Command::ScrollOffset) scrolls through
+//! a pixel at the time, wrapping around after 64 pixels. On a 128x64 display
+//! the top half of the screen shows the first 8 words of the rows and the
+//! bottom half the next 8, so each row holds a line on the left and the line
+//! 2 lines further on the right.
+//!
+//! [`Scroller`] writes the next lines into each row as it leaves the screen.
+//! The _Extended instruction set_ is left selected, with the graphic display off.
+
+use crate::ext;
+use crate::framebuffer::WORDS;
+
+/// Rows of characters in the DDRAM
+const ROWS: u8 = 4;
+/// Height of a row of characters, in pixels
+const ROW_HEIGHT: u8 = 16;
+
+/// Words of the given line, padded with spaces
+fn words(line: Option<&[u8]>) -> [u16; WORDS] {
+    let mut bytes = [b' '; 2 * WORDS];
+    if let Some(line) = line {
+        let len = line.len().min(bytes.len());
+        bytes[..len].copy_from_slice(&line[..len]);
+    }
+    core::array::from_fn(|i| u16::from_be_bytes([bytes[2 * i], bytes[2 * i + 1]]))
+}
+
+pub struct Scroller<T> {
+    lcd: T,
+    /// Line at the top of the screen
+    first: usize,
+    /// Scroll offset, in pixels
+    offset: u8,
+}
+
+impl<T> Scroller<T> {
+    pub fn new(lcd: T) -> Self {
+        Self {
+            lcd,
+            first: 0,
+            offset: 0,
+        }
+    }
+
+    /// The line at the top of the screen, and how many
+    /// of its pixel rows have scrolled out of it
+    pub fn position(&self) -> (usize, u8) {
+        (self.first, self.offset % ROW_HEIGHT)
+    }
+
+    pub fn release(self) -> T {
+        self.lcd
+    }
+}
+
+impl<T: ext::Execute> Scroller<T> {
+    fn load_row(&mut self, row: u8, line: usize, lines: &[&[u8]]) -> Result<(), T::Error> {
+        let addr = row * 0x10;
+        self.lcd.select_basic()?;
+        self.lcd.ddram_addr(addr)?;
+        self.lcd.write_burst(&words(lines.get(line).copied()))?;
+        self.lcd.ddram_addr(addr + WORDS as u8)?;
+        self.lcd.write_burst(&words(lines.get(line + 2).copied()))?;
+        self.lcd.select_extended()
+    }
+
+    /// Show the lines from the given one, lines past the end being blank
+    pub fn start(&mut self, lines: &[&[u8]], first: usize) -> Result<(), T::Error> {
+        (self.first, self.offset) = (first, 0);
+        for row in 0..ROWS {
+            self.load_row(row, first + row as usize, lines)?;
+        }
+        self.lcd.enable_scroll()?;
+        self.lcd.scroll_offset(0)
+    }
+
+    /// Scroll the lines up by a pixel, `lines` being the ones given to
+    /// [`start()`](Scroller::start) or the same list with lines added
+    pub fn step(&mut self, lines: &[&[u8]]) -> Result<(), T::Error> {
+        self.offset = (self.offset + 1) % (ROWS * ROW_HEIGHT);
+        self.lcd.scroll_offset(self.offset)?;
+        if !self.offset.is_multiple_of(ROW_HEIGHT) {
+            return Ok(());
+        }
+
+        // The row that just left the screen is shown again at the bottom
+        self.first += 1;
+        let row = (self.offset / ROW_HEIGHT + ROWS - 1) % ROWS;
+        self.load_row(row, self.first + ROWS as usize - 1, lines)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::ext::Execute as _;
+    use crate::mock::Emulator;
+
+    use super::*;
+
+    const LINES: [&[u8]; 8] = [
+        b"zero", b"one", b"two", b"three", b"four", b"five", b"six", b"seven",
+    ];
+
+    /// Line shown on the left and right half of the given DDRAM row
+    fn row(lcd: &Emulator, row: usize) -> [[u16; WORDS]; 2] {
+        let addr = row * 0x10;
+        let half = |start: usize| core::array::from_fn(|i| lcd.ddram[start + i]);
+        [half(addr), half(addr + WORDS)]
+    }
+
+    #[test]
+    fn rows_are_refilled_as_they_leave_the_screen() {
+        let mut lcd = Emulator::new();
+        lcd.select_extended().unwrap();
+        let mut scroller = Scroller::new(&mut lcd);
+        scroller.start(&LINES, 0).unwrap();
+        for _ in 0..5 {
+            scroller.step(&LINES).unwrap();
+        }
+        assert_eq!(scroller.position(), (0, 5));
+
+        for _ in 5..16 {
+            scroller.step(&LINES).unwrap();
+        }
+        assert_eq!(scroller.position(), (1, 0));
+        let expected = [words(Some(LINES[4])), words(Some(LINES[6]))];
+        assert_eq!(row(scroller.lcd, 0), expected);
+
+        for _ in 16..63 {
+            scroller.step(&LINES).unwrap();
+        }
+        assert_eq!(scroller.position(), (3, 15));
+        // Past the last pixel of the DDRAM the offset wraps to the first row
+        scroller.step(&LINES).unwrap();
+        assert_eq!(scroller.position(), (4, 0));
+        assert_eq!(lcd.scroll_offset, 0);
+        assert_eq!(row(&lcd, 3), [words(Some(LINES[7])), words(None)]);
+        assert!(lcd.extended && !lcd.graphic);
+    }
+}
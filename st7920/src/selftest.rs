@@ -0,0 +1,360 @@
+//! Data bus integrity self-test
+//!
+//! [`run()`] writes walking ones and walking zeros to the DDRAM, the CGRAM
+//! and the GDRAM, reads them back and looks at which data lines don't
+//! follow the patterns, producing a [`Report`].
+//!
+//! Swapped lines swap the data back when reading, so they can't be seen in
+//! the RAM contents: they are found by reading the Address Counter after
+//! each write, as the controller counts with the lines in the right order.
+//! Only swaps involving DB0-4 can be found, since up to 16 words are written.
+//!
+//! The contents of the RAMs are overwritten, and the _Basic instruction set_
+//! is selected at the end.
+//!
+//! On a 4-bit bus each line carries two bits of every byte: a fault on DB4
+//! shows up on both bit 0 and bit 4.
+
+use core::fmt;
+
+use crate::{ext, ExecuteRead};
+
+/// Walking ones followed by walking zeros
+const PATTERNS: [u8; 16] = {
+    let mut patterns = [0; 16];
+    let mut i = 0;
+    while i < 8 {
+        patterns[i] = 1 << i;
+        patterns[8 + i] = !(1 << i);
+        i += 1;
+    }
+    patterns
+};
+
+/// State of a data line
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Line {
+    Ok,
+    /// Always read high
+    StuckHigh,
+    /// Always read low
+    StuckLow,
+    /// Swapped with the given line
+    Swapped(u8),
+    /// Pulled along by the lines in the mask
+    Shorted(u8),
+    /// Doesn't follow its own patterns consistently, e.g. not connected
+    Unreliable,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Report {
+    /// State of DB0 to DB7
+    pub lines: [Line; 8],
+    /// Words read back wrong from the DDRAM
+    pub ddram_errors: u8,
+    /// Words read back wrong from the CGRAM
+    pub cgram_errors: u8,
+    /// Words read back wrong from the GDRAM
+    pub gdram_errors: u8,
+    /// Writes to the DDRAM after which the Address Counter
+    /// was not the number of words written so far
+    pub address_counter_errors: u8,
+}
+
+impl Report {
+    pub fn ok(&self) -> bool {
+        self.lines.iter().all(|l| *l == Line::Ok)
+            && self.ddram_errors == 0
+            && self.cgram_errors == 0
+            && self.gdram_errors == 0
+            && self.address_counter_errors == 0
+    }
+}
+
+impl fmt::Display for Report {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for (i, line) in self.lines.iter().enumerate() {
+            match line {
+                Line::Ok => continue,
+                Line::Swapped(j) => writeln!(f, "DB{i}: swapped with DB{j}")?,
+                Line::Shorted(mask) => writeln!(f, "DB{i}: shorted to 0b{mask:08b}")?,
+                _ => writeln!(f, "DB{i}: {line:?}")?,
+            }
+        }
+        write!(
+            f,
+            "errors: DDRAM {}, CGRAM {}, GDRAM {}, AC {}",
+            self.ddram_errors, self.cgram_errors, self.gdram_errors, self.address_counter_errors
+        )
+    }
+}
+
+/// Bits read back from a RAM for each pattern
+#[derive(Clone, Copy)]
+struct Reads {
+    /// Bits read high at least once
+    any: [u8; 16],
+    /// Bits always read high
+    all: [u8; 16],
+    /// Words read back wrong
+    errors: u8,
+}
+
+impl Reads {
+    fn new(words: [u16; 16]) -> Self {
+        let mut reads = Self {
+            any: [0; 16],
+            all: [0xFF; 16],
+            errors: 0,
+        };
+        for (p, word) in words.into_iter().enumerate() {
+            let pattern = PATTERNS[p];
+            let [h, l] = word.to_be_bytes();
+            reads.any[p] |= h | l;
+            reads.all[p] &= h & l;
+            reads.errors += (h != pattern || l != pattern) as u8;
+        }
+        reads
+    }
+}
+
+struct Analysis {
+    /// DDRAM, CGRAM and GDRAM
+    rams: [Reads; 3],
+    /// Address Counter after each write to the DDRAM
+    counter: [u8; 16],
+}
+
+impl Analysis {
+    fn line(&self, i: usize) -> Line {
+        let bit = 1 << i;
+        let reads = self.rams.iter().flat_map(|r| r.any.iter().zip(&r.all));
+        let (stuck_high, stuck_low) = reads.fold((0xFF, 0xFF), |(high, low), (any, all)| {
+            (high & all, low & !any)
+        });
+        if stuck_high & bit != 0 {
+            return Line::StuckHigh;
+        }
+        if stuck_low & bit != 0 {
+            return Line::StuckLow;
+        }
+
+        // When an address command is corrupted the data is read from the
+        // wrong place, so only the RAM with the fewest errors is looked at
+        let ram = self.rams.iter().min_by_key(|r| r.errors).unwrap();
+        let (one, zero) = (i, 8 + i);
+        // Lines raised by this one, and lines lowered by this one
+        let raised = ram.any[one] & !bit & !stuck_high;
+        let lowered = !ram.all[zero] & !bit & !stuck_low;
+        if raised | lowered != 0 {
+            return Line::Shorted(raised | lowered);
+        }
+
+        if let Some(j) = self.swapped(i) {
+            return Line::Swapped(j);
+        }
+
+        match ram.all[one] & bit == 0 || ram.any[zero] & bit != 0 {
+            true => Line::Unreliable,
+            false => Line::Ok,
+        }
+    }
+
+    /// The line swapped with DB`i`, according to the Address Counter
+    /// read after writing 1, 2, 4, 8 and 16 words
+    fn swapped(&self, i: usize) -> Option<u8> {
+        (0..5).find_map(|k| {
+            let read = self.counter[(1 << k) - 1];
+            let j = read.trailing_zeros() as usize;
+            match read.count_ones() == 1 && j != k {
+                true if i == k => Some(j as u8),
+                true if i == j => Some(k as u8),
+                _ => None,
+            }
+        })
+    }
+}
+
+fn words() -> impl Iterator<Item = u16> {
+    PATTERNS.into_iter().map(|p| (p as u16) << 8 | p as u16)
+}
+
+/// Read back the patterns, after the address was selected again
+fn read_back<Lcd: ExecuteRead>(lcd: &mut Lcd) -> Result<[u16; 16], Lcd::Error> {
+    // The first read after setting the address returns stale data
+    lcd.read()?;
+    let mut words = [0; 16];
+    for word in &mut words {
+        *word = lcd.read()?;
+    }
+    Ok(words)
+}
+
+/// Run the self-test, see the [module](self) documentation
+pub fn run<Lcd, E>(mut lcd: Lcd) -> Result<Report, E>
+where
+    Lcd: ext::Execute<Error = E> + ExecuteRead<Error = E>,
+{
+    let mut counter = [0; 16];
+
+    lcd.select_basic()?;
+    lcd.ddram_addr(0)?;
+    for (word, counter) in words().zip(&mut counter) {
+        lcd.write(word)?;
+        *counter = lcd.read_address_counter()?;
+    }
+    lcd.ddram_addr(0)?;
+    let ddram = Reads::new(read_back(&mut lcd)?);
+
+    lcd.cgram_addr(0)?;
+    lcd.write_iter(words())?;
+    lcd.cgram_addr(0)?;
+    let cgram = Reads::new(read_back(&mut lcd)?);
+
+    lcd.select_extended()?;
+    lcd.select_graphic()?;
+    lcd.graphic_ram_addr(0, 0)?;
+    lcd.write_iter(words())?;
+    lcd.graphic_ram_addr(0, 0)?;
+    let gdram = Reads::new(read_back(&mut lcd)?);
+    // Turn the graphic display off before going back
+    lcd.select_extended()?;
+    lcd.select_basic()?;
+
+    let analysis = Analysis {
+        rams: [ddram, cgram, gdram],
+        counter,
+    };
+    Ok(Report {
+        lines: core::array::from_fn(|i| analysis.line(i)),
+        ddram_errors: ddram.errors,
+        cgram_errors: cgram.errors,
+        gdram_errors: gdram.errors,
+        address_counter_errors: (counter.iter().zip(1..))
+            .filter(|(read, n)| **read != *n)
+            .count() as u8,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use core::convert::Infallible;
+
+    use crate::ext::Execute as _;
+    use crate::mock::Emulator;
+    use crate::{Command, Execute};
+
+    use super::*;
+
+    /// An emulated controller behind a data bus that changes every byte
+    /// crossing it, both ways
+    struct Faulty {
+        lcd: Emulator,
+        fault: fn(u8) -> u8,
+    }
+
+    impl Faulty {
+        /// Decode the instruction the controller receives,
+        /// following the instruction set it has selected
+        fn send(&mut self, bytes: [u8; 2]) -> Result<(), Infallible> {
+            let bytes = bytes.map(self.fault);
+            match self.lcd.extended {
+                true => match ext::Command::try_from(bytes) {
+                    Ok(command) => self.lcd.execute_ext(command),
+                    Err(byte) => self.lcd.execute(Command::try_from(byte).unwrap()),
+                },
+                false => match Command::try_from(bytes[0]) {
+                    Ok(command) => self.lcd.execute(command),
+                    Err(byte) => self.lcd.execute_ext([byte, bytes[1]].try_into().unwrap()),
+                },
+            }
+        }
+    }
+
+    impl Execute for Faulty {
+        type Error = Infallible;
+
+        fn execute(&mut self, command: Command) -> Result<(), Self::Error> {
+            match command {
+                Command::Write(data) => {
+                    let data = data.to_be_bytes().map(self.fault);
+                    self.lcd.write(u16::from_be_bytes(data))
+                }
+                _ => self.send([command.into_byte(), 0]),
+            }
+        }
+    }
+
+    impl ext::Execute for Faulty {
+        fn execute_ext(&mut self, command: ext::Command) -> Result<(), Self::Error> {
+            self.send(command.into_bytes())
+        }
+    }
+
+    impl ExecuteRead for Faulty {
+        type Error = Infallible;
+
+        fn read(&mut self) -> Result<u16, Self::Error> {
+            let data = self.lcd.read()?.to_be_bytes().map(self.fault);
+            Ok(u16::from_be_bytes(data))
+        }
+
+        fn read_bf_ac(&mut self) -> Result<(bool, u8), Self::Error> {
+            let (busy, ac) = self.lcd.read_bf_ac()?;
+            let byte = (self.fault)((busy as u8) << 7 | ac);
+            Ok((byte & 0x80 != 0, byte & 0x7F))
+        }
+    }
+
+    fn run_with(fault: fn(u8) -> u8) -> Report {
+        let lcd = Faulty {
+            lcd: Emulator::new(),
+            fault,
+        };
+        run(lcd).unwrap()
+    }
+
+    #[test]
+    fn healthy_bus_is_ok() {
+        let report = run_with(|byte| byte);
+        assert!(report.ok(), "{report}");
+    }
+
+    #[test]
+    fn stuck_lines_are_found() {
+        let report = run_with(|byte| byte & !0b1);
+        let mut lines = [Line::Ok; 8];
+        lines[0] = Line::StuckLow;
+        assert_eq!(report.lines, lines);
+        // Every pattern with DB0 high
+        let rams = [
+            report.ddram_errors,
+            report.cgram_errors,
+            report.gdram_errors,
+        ];
+        assert_eq!(rams, [8; 3]);
+        assert_eq!(report.address_counter_errors, 8);
+    }
+
+    #[test]
+    fn swapped_lines_are_found() {
+        let swap = |byte: u8| {
+            let (db1, db3) = (byte >> 1 & 1, byte >> 3 & 1);
+            byte & !0b1010 | db1 << 3 | db3 << 1
+        };
+        let report = run_with(swap);
+        let mut lines = [Line::Ok; 8];
+        lines[1] = Line::Swapped(3);
+        lines[3] = Line::Swapped(1);
+        assert_eq!(report.lines, lines);
+        // The data is swapped back when read
+        let rams = [
+            report.ddram_errors,
+            report.cgram_errors,
+            report.gdram_errors,
+        ];
+        assert_eq!(rams, [0; 3]);
+        assert_ne!(report.address_counter_errors, 0);
+    }
+}
@@ -1,7 +1,11 @@
 use either::Either::{self, Left, Right};
 use embedded_hal::{digital::OutputPin, spi::SpiBus};
 
-use crate::{ext, hal, Command, Execute, SharedBus};
+use crate::{ext, hal, Command, Execute, Oscillator, SharedBus};
+
+pub mod bitbang;
+pub mod device;
+pub mod frame;
 
 fn sync(rs: u8) -> u8 {
     0b11111000 | rs << 1
@@ -24,13 +28,26 @@ struct Pin<Cs> {
 pub struct Interface<Spi, Cs, const PINS: usize> {
     spi: Spi,
     pins: [Pin<Cs>; PINS],
+    oscillator: Oscillator,
 }
 
 impl<Spi, Cs, const PINS: usize> Interface<Spi, Cs, PINS> {
     pub fn new(spi: Spi, cs: [Cs; PINS]) -> Self {
         let end = hal::now();
         let pins = cs.map(|cs| Pin { cs, end });
-        Self { spi, pins }
+        let oscillator = Oscillator::default();
+        Self {
+            spi,
+            pins,
+            oscillator,
+        }
+    }
+
+    /// Compute the execution times for the given oscillator
+    /// instead of the [nominal](Oscillator::NOMINAL) one
+    pub fn with_oscillator(mut self, oscillator: Oscillator) -> Self {
+        self.oscillator = oscillator;
+        self
     }
 }
 
@@ -49,6 +66,7 @@ impl<Spi, Cs, const PINS: usize> SharedBus for Interface<Spi, Cs, PINS> {
         self.pins.get_mut(idx).map(|Pin { cs, end }| Interface {
             spi: &mut self.spi,
             pins: [Pin { cs, end: *end }],
+            oscillator: self.oscillator,
         })
     }
 }
@@ -71,36 +89,164 @@ impl<Spi, Cs: OutputPin> Interface<Spi, Cs, 1> {
     }
 }
 
+impl<Spi: SpiBus, Cs: OutputPin> Interface<Spi, Cs, 1> {
+    /// Send a whole encoded frame in a single transmission,
+    /// following the pauses of its plan
+    ///
+    /// The frame should be encoded with the same oscillator as this interface.
+    pub fn send_frame(
+        &mut self,
+        frame: &frame::Encoder<'_>,
+    ) -> Result<(), Either<Spi::Error, Cs::Error>> {
+        let Some(last) = frame.plan().last() else {
+            return Ok(());
+        };
+        self.transaction(last.pause, |spi| {
+            for (i, (chunk, pause)) in frame.chunks().enumerate() {
+                spi.write(chunk)?;
+                if i != frame.plan().len() - 1 {
+                    spi.flush()?;
+                    hal::sleep(pause);
+                }
+            }
+            spi.flush()
+        })
+    }
+}
+
 impl<Spi: SpiBus, Cs: OutputPin> Execute for Interface<Spi, Cs, 1> {
     type Error = Either<Spi::Error, Cs::Error>;
 
     fn execute(&mut self, command: Command) -> Result<(), Self::Error> {
-        self.transaction(command.execution_time(), |spi| match command {
+        let duration = command.execution_time_at(&self.oscillator);
+        self.transaction(duration, |spi| match command {
             Command::Write(data) => spi.write(&encode_u16(1, data)),
             _ => spi.write(&encode_u8(0, command.into_byte())),
         })
     }
+
+    fn write_burst(&mut self, data: &[u16]) -> Result<(), Self::Error> {
+        self.write_iter(data.iter().copied())
+    }
+
+    /// Write all the words in a single transmission
+    ///
+    /// The controller accepts consecutive data after a single synchronization
+    /// byte, so CS is kept high for the whole burst and the synchronization byte
+    /// is only sent once. Between each word the SPI bus is flushed and the
+    /// execution time of the write is waited.
+    ///
+    /// CS stays high, and the SPI bus busy, for the whole iterator: with the
+    /// nominal oscillator that's at least 72µs per word, about 37ms for the
+    /// whole GDRAM. Split the data into shorter bursts to access the other
+    /// displays of the bus in between.
+    fn write_iter(&mut self, data: impl IntoIterator<Item = u16>) -> Result<(), Self::Error> {
+        let duration = Command::Write(0).execution_time_at(&self.oscillator);
+        self.transaction(duration, |spi| {
+            spi.write(&[sync(1)])?;
+            for (i, word) in data.into_iter().enumerate() {
+                if i != 0 {
+                    spi.flush()?;
+                    hal::sleep(duration);
+                }
+                let [_, bytes @ ..] = encode_u16(1, word);
+                spi.write(&bytes)?;
+            }
+            spi.flush()
+        })
+    }
 }
 
 impl<Spi: SpiBus, Cs: OutputPin> ext::Execute for Interface<Spi, Cs, 1> {
     fn execute_ext(&mut self, command: ext::Command) -> Result<(), Self::Error> {
-        self.transaction(command.execution_time(), |spi| match command.into_bytes() {
+        let duration = command.execution_time_at(&self.oscillator);
+        self.transaction(duration, |spi| match command.into_bytes() {
             [data, 0] => spi.write(&encode_u8(0, data)),
             [h, l] => spi.write(&encode_u16(0, (h as u16) << 8 | l as u16)),
         })
     }
 }
 
-impl<Spi: SpiBus, Cs: OutputPin> Execute for &mut Interface<Spi, Cs, 1> {
-    type Error = Either<Spi::Error, Cs::Error>;
+#[cfg(test)]
+mod tests {
+    use std::vec::Vec;
 
-    fn execute(&mut self, command: Command) -> Result<(), Self::Error> {
-        Interface::<Spi, Cs, 1>::execute(self, command)
+    use crate::hal::{clock, Duration};
+    use crate::mock::{Pin, Recorder, Spi};
+
+    use super::*;
+
+    fn interface(recorder: &Recorder) -> Interface<Spi, Pin, 1> {
+        clock::reset();
+        let spi = recorder.spi("SCLK", "SID", Duration::micros(1));
+        Interface::new(spi, [recorder.pin("CS")])
     }
-}
 
-impl<Spi: SpiBus, Cs: OutputPin> ext::Execute for &mut Interface<Spi, Cs, 1> {
-    fn execute_ext(&mut self, command: ext::Command) -> Result<(), Self::Error> {
-        Interface::<Spi, Cs, 1>::execute_ext(self, command)
+    #[test]
+    fn bursts_are_sent_in_a_single_transmission() {
+        let words = [0x4142, 0x4344, 0x4546];
+        let recorder = Recorder::new();
+        let mut lcd = interface(&recorder);
+        let ((), burst) = clock::measure(|| lcd.write_iter(words).unwrap());
+
+        // A single synchronization byte, followed by the halves of each word
+        let data = words
+            .iter()
+            .flat_map(|&word| encode_u16(1, word)[1..].to_vec());
+        let expected: Vec<_> = [sync(1)].into_iter().chain(data).collect();
+        assert_eq!(recorder.shifted("SCLK", "SID"), expected);
+
+        // CS is high around every bit
+        let cs = recorder.transitions("CS");
+        let sclk = recorder.transitions("SCLK");
+        assert_eq!(
+            cs.iter().map(|&(_, level)| level).collect::<Vec<_>>(),
+            [true, false]
+        );
+        assert!(cs[0].0 <= sclk[0].0 && sclk[sclk.len() - 1].0 <= cs[1].0);
+
+        let recorder = Recorder::new();
+        let mut lcd = interface(&recorder);
+        let ((), separate) = clock::measure(|| {
+            for word in words {
+                lcd.write(word).unwrap();
+            }
+        });
+        assert_eq!(recorder.shifted("SCLK", "SID").len(), 5 * words.len());
+        assert!(burst < separate, "{burst} >= {separate}");
+    }
+
+    #[test]
+    fn frames_pause_between_the_chunks() {
+        let empty = frame::Segment {
+            end: 0,
+            pause: Duration::from_ticks(0),
+        };
+        let (mut buf, mut plan) = ([0; 16], [empty; 4]);
+        let mut frame = frame::Encoder::new(&mut buf, &mut plan, Oscillator::NOMINAL);
+        frame.push(Command::Clear).unwrap();
+        frame.push(Command::Write(0x4142)).unwrap();
+        frame.push(Command::Write(0x4344)).unwrap();
+
+        let recorder = Recorder::new();
+        let mut lcd = interface(&recorder);
+        lcd.send_frame(&frame).unwrap();
+        assert_eq!(recorder.shifted("SCLK", "SID"), frame.bytes());
+        assert_eq!(recorder.transitions("CS").len(), 2);
+
+        // Between the last bit of a chunk and the first one of the next
+        let sclk: Vec<_> = recorder
+            .transitions("SCLK")
+            .into_iter()
+            .filter(|&(_, level)| level)
+            .map(|(time, _)| time)
+            .collect();
+        let starts = frame.plan().iter().map(|segment| segment.end * 8);
+        for (start, segment) in starts.zip(frame.plan()).take(2) {
+            assert!(sclk[start] - sclk[start - 1] >= segment.pause);
+        }
+        // The next transmission waits for the last write
+        let next = lcd.pins[0].end;
+        assert_eq!(next, clock::now() + Duration::micros(72));
     }
 }
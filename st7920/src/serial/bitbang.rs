@@ -0,0 +1,112 @@
+//! Software SPI over plain output pins
+//!
+//! [`BitBang`] implements [`SpiBus`] by toggling two [`OutputPin`]s,
+//! so it can be used with [`Interface`](super::Interface) on boards
+//! where SID and SCLK are not routed to an SPI peripheral.
+//!
+//! The clock idles low and SID is changed on its falling edge,
+//! as the ST7920 samples it on the rising edge.
+//! There is no MISO line: reads clock out zeroes and return zeroes.
+
+use core::fmt::Debug;
+
+use embedded_hal::digital::OutputPin;
+use embedded_hal::spi::{self, ErrorKind, SpiBus};
+use fugit::NanosDurationU32;
+
+use crate::hal;
+
+/// Error of one of the pins
+#[derive(Debug)]
+pub struct PinError<E>(pub E);
+
+impl<E: Debug> spi::Error for PinError<E> {
+    fn kind(&self) -> ErrorKind {
+        ErrorKind::Other
+    }
+}
+
+pub struct BitBang<Sclk, Sid> {
+    sclk: Sclk,
+    sid: Sid,
+    half: hal::Duration,
+}
+
+impl<Sclk, Sid> BitBang<Sclk, Sid> {
+    /// Create a new bus clocked with the given period
+    ///
+    /// Each half of the period is rounded up to whole microseconds,
+    /// with zero the pins are toggled as fast as possible.
+    /// Both pins are expected to be already low.
+    pub fn new(sclk: Sclk, sid: Sid, period: NanosDurationU32) -> Self {
+        let half = hal::Duration::from_ticks((period.ticks() / 2).div_ceil(1000) as u64);
+        Self { sclk, sid, half }
+    }
+
+    pub fn release(self) -> (Sclk, Sid) {
+        (self.sclk, self.sid)
+    }
+}
+
+impl<Sclk, Sid, E> BitBang<Sclk, Sid>
+where
+    Sclk: OutputPin<Error = E>,
+    Sid: OutputPin<Error = E>,
+{
+    fn shift(&mut self, byte: u8) -> Result<(), E> {
+        for bit in (0..8).rev() {
+            self.sid.set_state((byte & 1 << bit != 0).into())?;
+            hal::sleep(self.half);
+            self.sclk.set_high()?;
+            hal::sleep(self.half);
+            self.sclk.set_low()?;
+        }
+        Ok(())
+    }
+}
+
+impl<Sclk, Sid, E: Debug> spi::ErrorType for BitBang<Sclk, Sid>
+where
+    Sclk: OutputPin<Error = E>,
+    Sid: OutputPin<Error = E>,
+{
+    type Error = PinError<E>;
+}
+
+impl<Sclk, Sid, E: Debug> SpiBus for BitBang<Sclk, Sid>
+where
+    Sclk: OutputPin<Error = E>,
+    Sid: OutputPin<Error = E>,
+{
+    fn read(&mut self, words: &mut [u8]) -> Result<(), Self::Error> {
+        words.fill(0);
+        words
+            .iter()
+            .try_for_each(|_| self.shift(0))
+            .map_err(PinError)
+    }
+
+    fn write(&mut self, words: &[u8]) -> Result<(), Self::Error> {
+        words
+            .iter()
+            .try_for_each(|&w| self.shift(w))
+            .map_err(PinError)
+    }
+
+    fn transfer(&mut self, read: &mut [u8], write: &[u8]) -> Result<(), Self::Error> {
+        self.write(write)?;
+        let extra = read.len().saturating_sub(write.len());
+        read.fill(0);
+        (0..extra).try_for_each(|_| self.shift(0)).map_err(PinError)
+    }
+
+    fn transfer_in_place(&mut self, words: &mut [u8]) -> Result<(), Self::Error> {
+        self.write(words)?;
+        words.fill(0);
+        Ok(())
+    }
+
+    fn flush(&mut self) -> Result<(), Self::Error> {
+        Ok(())
+    }
+}
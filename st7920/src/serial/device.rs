@@ -0,0 +1,187 @@
+//! Serial interface on top of an [`SpiDevice`]
+//!
+//! Unlike [`Interface`](super::Interface), which owns the whole [`SpiBus`]
+//! and drives CS by itself, [`Device`] leaves the bus arbitration and CS
+//! to the [`SpiDevice`] implementation (like the ones of `embedded-hal-bus`),
+//! so that the bus can be shared with other peripherals.
+//!
+//! The ST7920 CS is active-high, while [`SpiDevice`]s assert CS by
+//! driving it low: wrap the CS pin in [`ActiveHigh`] before
+//! handing it to the device.
+//!
+//! The execution time of each command is waited with a delay operation
+//! at the end of its transaction, while CS is still asserted: the bus stays
+//! held for 1.6ms on [`Clear`](Command::Clear) and [`Home`](Command::Home),
+//! and for up to 16 × 72µs on each transaction of
+//! [`write_iter()`](Execute::write_iter) (with the nominal oscillator).
+//!
+//! [`SpiBus`]: embedded_hal::spi::SpiBus
+
+use embedded_hal::digital::{self, OutputPin};
+use embedded_hal::spi::{Operation, SpiDevice};
+
+use super::{encode_u16, encode_u8, sync};
+use crate::{ext, hal, Command, Execute, Oscillator};
+
+/// Inverts the levels of an output pin
+pub struct ActiveHigh<P>(pub P);
+
+impl<P: OutputPin> digital::ErrorType for ActiveHigh<P> {
+    type Error = P::Error;
+}
+
+impl<P: OutputPin> OutputPin for ActiveHigh<P> {
+    fn set_low(&mut self) -> Result<(), Self::Error> {
+        self.0.set_high()
+    }
+
+    fn set_high(&mut self) -> Result<(), Self::Error> {
+        self.0.set_low()
+    }
+}
+
+/// Words sent in each transaction by [`Execute::write_iter()`]
+const BURST: usize = 16;
+
+pub struct Device<D> {
+    device: D,
+    oscillator: Oscillator,
+}
+
+fn nanos(duration: hal::Duration) -> u32 {
+    duration.to_nanos().try_into().unwrap_or(u32::MAX)
+}
+
+impl<D> Device<D> {
+    pub fn new(device: D) -> Self {
+        let oscillator = Oscillator::default();
+        Self { device, oscillator }
+    }
+
+    /// Compute the execution times for the given oscillator
+    /// instead of the [nominal](Oscillator::NOMINAL) one
+    pub fn with_oscillator(mut self, oscillator: Oscillator) -> Self {
+        self.oscillator = oscillator;
+        self
+    }
+
+    pub fn release(self) -> D {
+        self.device
+    }
+}
+
+impl<D: SpiDevice> Device<D> {
+    fn send(&mut self, bytes: &[u8], duration: hal::Duration) -> Result<(), D::Error> {
+        self.device
+            .transaction(&mut [Operation::Write(bytes), Operation::DelayNs(nanos(duration))])
+    }
+}
+
+impl<D: SpiDevice> Execute for Device<D> {
+    type Error = D::Error;
+
+    fn execute(&mut self, command: Command) -> Result<(), Self::Error> {
+        let duration = command.execution_time_at(&self.oscillator);
+        match command {
+            Command::Write(data) => self.send(&encode_u16(1, data), duration),
+            _ => self.send(&encode_u8(0, command.into_byte()), duration),
+        }
+    }
+
+    fn write_burst(&mut self, data: &[u16]) -> Result<(), Self::Error> {
+        self.write_iter(data.iter().copied())
+    }
+
+    /// Write the words in transactions of up to 16 words each
+    ///
+    /// Each transaction starts with a single synchronization byte,
+    /// followed by the words, each one with a delay operation for
+    /// its execution time.
+    fn write_iter(&mut self, data: impl IntoIterator<Item = u16>) -> Result<(), Self::Error> {
+        let delay = nanos(Command::Write(0).execution_time_at(&self.oscillator));
+        let mut data = data.into_iter().peekable();
+        while data.peek().is_some() {
+            let mut bytes = [0; 4 * BURST];
+            let mut len = 0;
+            for (chunk, word) in bytes.chunks_exact_mut(4).zip(data.by_ref()) {
+                let [_, encoded @ ..] = encode_u16(1, word);
+                chunk.copy_from_slice(&encoded);
+                len += 1;
+            }
+
+            let sync = [sync(1)];
+            let mut words = bytes.chunks_exact(4);
+            let mut operations: [_; 1 + 2 * BURST] = core::array::from_fn(|i| match i {
+                0 => Operation::Write(&sync),
+                _ if i % 2 == 1 => Operation::Write(words.next().unwrap()),
+                _ => Operation::DelayNs(delay),
+            });
+            self.device.transaction(&mut operations[..1 + 2 * len])?;
+        }
+        Ok(())
+    }
+}
+
+impl<D: SpiDevice> ext::Execute for Device<D> {
+    fn execute_ext(&mut self, command: ext::Command) -> Result<(), Self::Error> {
+        let duration = command.execution_time_at(&self.oscillator);
+        match command.into_bytes() {
+            [data, 0] => self.send(&encode_u8(0, data), duration),
+            [h, l] => self.send(&encode_u16(0, (h as u16) << 8 | l as u16), duration),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use core::cell::RefCell;
+    use std::vec::Vec;
+
+    use embedded_hal_bus::spi::RefCellDevice;
+
+    use crate::hal::{clock, Duration};
+    use crate::mock::{Delay, Recorder};
+
+    use super::*;
+
+    /// How long CS was asserted in each transaction
+    fn transactions(recorder: &Recorder) -> Vec<Duration> {
+        let cs = recorder.transitions("CS");
+        cs.chunks(2)
+            .map(|pulse| match pulse {
+                [(start, true), (end, false)] => *end - *start,
+                _ => panic!("unexpected CS levels: {pulse:?}"),
+            })
+            .collect()
+    }
+
+    #[test]
+    fn delays_hold_the_bus() {
+        clock::reset();
+        let recorder = Recorder::new();
+        let bus = RefCell::new(recorder.spi("SCLK", "SID", Duration::micros(1)));
+        let cs = ActiveHigh(recorder.pin("CS"));
+        let device = RefCellDevice::new(&bus, cs, Delay).unwrap();
+        let mut lcd = Device::new(device);
+
+        lcd.clear().unwrap();
+        lcd.write_burst(&[0x4142; BURST + 4]).unwrap();
+
+        let bytes = |words| 1 + 4 * words;
+        let shifted = recorder.shifted("SCLK", "SID");
+        assert_eq!(shifted.len(), 3 + bytes(BURST) + bytes(4));
+        assert_eq!(shifted[3], sync(1));
+        assert_eq!(shifted[3 + bytes(BURST)], sync(1));
+
+        let bits = |bytes| Duration::micros(8 * bytes as u64);
+        let write = Duration::micros(72);
+        let expected = [
+            bits(3) + Duration::micros(1_600),
+            bits(bytes(BURST)) + write * BURST as u32,
+            bits(bytes(4)) + write * 4,
+        ];
+        assert_eq!(transactions(&recorder), expected);
+        let total = expected.into_iter().reduce(|a, b| a + b).unwrap();
+        assert_eq!(clock::elapsed(), total);
+    }
+}
@@ -0,0 +1,189 @@
+//! Encoding of whole frames for a single SPI (or DMA) transfer
+//!
+//! The [`Encoder`] writes the sync-framed bytes of many commands into a
+//! caller-provided buffer, together with a plan of the pauses needed
+//! between them to respect the execution time of each command.
+//!
+//! Like with [`Execute::write_iter()`](crate::Execute::write_iter) a
+//! synchronization byte is only added when the selected register changes,
+//! so CS can be kept high for the whole frame.
+//! The buffer can be transferred by an SPI bus, or a DMA engine, one chunk
+//! at the time, waiting for the pause after each one.
+
+use super::{encode_u16, encode_u8, sync};
+use crate::{ext, hal, Command, Oscillator};
+
+/// A chunk of the encoded bytes, followed by a pause
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Segment {
+    /// End (exclusive) of the chunk in the buffer,
+    /// the chunk starts at the end of the previous one
+    pub end: usize,
+    /// How long to wait after the chunk was transferred
+    pub pause: hal::Duration,
+}
+
+/// The buffer or the plan is too small for the command
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Full;
+
+pub struct Encoder<'a> {
+    buf: &'a mut [u8],
+    len: usize,
+    plan: &'a mut [Segment],
+    segments: usize,
+    /// Register selected by the last synchronization byte
+    rs: Option<u8>,
+    oscillator: Oscillator,
+}
+
+impl<'a> Encoder<'a> {
+    /// Create an encoder computing the pauses for the given oscillator
+    ///
+    /// Each command takes one [`Segment`] of the plan, and up to five bytes of the buffer.
+    pub fn new(buf: &'a mut [u8], plan: &'a mut [Segment], oscillator: Oscillator) -> Self {
+        Self {
+            buf,
+            len: 0,
+            plan,
+            segments: 0,
+            rs: None,
+            oscillator,
+        }
+    }
+
+    /// The encoded bytes
+    pub fn bytes(&self) -> &[u8] {
+        &self.buf[..self.len]
+    }
+
+    /// The chunks of [`bytes()`](Self::bytes) to transfer, each followed by a pause
+    pub fn plan(&self) -> &[Segment] {
+        &self.plan[..self.segments]
+    }
+
+    /// Iterate over the chunks of bytes together with the pause that follows them
+    pub fn chunks(&self) -> impl Iterator<Item = (&[u8], hal::Duration)> {
+        let starts = core::iter::once(0).chain(self.plan().iter().map(|s| s.end));
+        let plan = self.plan().iter().zip(starts);
+        plan.map(|(s, start)| (&self.buf[start..s.end], s.pause))
+    }
+
+    /// Remove everything that was encoded so far
+    pub fn clear(&mut self) {
+        self.len = 0;
+        self.segments = 0;
+        self.rs = None;
+    }
+
+    pub fn push(&mut self, command: Command) -> Result<(), Full> {
+        let duration = command.execution_time_at(&self.oscillator);
+        match command {
+            Command::Write(data) => self.append(1, &encode_u16(1, data)[1..], duration),
+            _ => self.append(0, &encode_u8(0, command.into_byte())[1..], duration),
+        }
+    }
+
+    pub fn push_ext(&mut self, command: ext::Command) -> Result<(), Full> {
+        let duration = command.execution_time_at(&self.oscillator);
+        match command.into_bytes() {
+            [data, 0] => self.append(0, &encode_u8(0, data)[1..], duration),
+            [h, l] => self.append(0, &encode_u16(0, (h as u16) << 8 | l as u16)[1..], duration),
+        }
+    }
+
+    /// Append the bytes of a command, preceded by a synchronization
+    /// byte when the register changes
+    fn append(&mut self, rs: u8, bytes: &[u8], duration: hal::Duration) -> Result<(), Full> {
+        let header = (self.rs != Some(rs)) as usize;
+        let end = self.len + header + bytes.len();
+        if end > self.buf.len() || self.segments == self.plan.len() {
+            return Err(Full);
+        }
+
+        if header == 1 {
+            self.buf[self.len] = sync(rs);
+        }
+        self.buf[self.len + header..end].copy_from_slice(bytes);
+        self.len = end;
+        self.rs = Some(rs);
+
+        self.plan[self.segments] = Segment {
+            end,
+            pause: duration,
+        };
+        self.segments += 1;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::vec::Vec;
+
+    use super::*;
+
+    const EMPTY: Segment = Segment {
+        end: 0,
+        pause: hal::Duration::from_ticks(0),
+    };
+
+    #[test]
+    fn sync_bytes_are_sent_when_the_register_changes() {
+        let (mut buf, mut plan) = ([0; 32], [EMPTY; 8]);
+        let mut frame = Encoder::new(&mut buf, &mut plan, Oscillator::NOMINAL);
+        frame.push(Command::DdRamAddr(0x10)).unwrap();
+        frame.push(Command::Write(0x4142)).unwrap();
+        frame.push(Command::Write(0x4344)).unwrap();
+        frame.push_ext(ext::Command::SelectExtended).unwrap();
+        frame
+            .push_ext(ext::Command::GraphicRamAddr { y: 1, x: 2 })
+            .unwrap();
+
+        let bytes = [
+            [0xF8, 0x90, 0x00].as_slice(),
+            &[0xFA, 0x40, 0x10, 0x40, 0x20],
+            &[0x40, 0x30, 0x40, 0x40],
+            &[0xF8, 0x20, 0x40],
+            &[0x80, 0x10, 0x80, 0x20],
+        ];
+        assert_eq!(frame.bytes(), bytes.concat());
+        let chunks: Vec<_> = frame.chunks().map(|(chunk, _)| chunk).collect();
+        assert_eq!(chunks, bytes);
+    }
+
+    #[test]
+    fn each_command_is_followed_by_its_execution_time() {
+        let (mut buf, mut plan) = ([0; 32], [EMPTY; 8]);
+        let mut frame = Encoder::new(&mut buf, &mut plan, Oscillator::NOMINAL);
+        frame.push(Command::Clear).unwrap();
+        frame.push(Command::Write(0x4142)).unwrap();
+        frame.push_ext(ext::Command::SelectExtended).unwrap();
+
+        let pause = |end, micros| Segment {
+            end,
+            pause: hal::Duration::micros(micros),
+        };
+        assert_eq!(frame.plan(), [pause(3, 1_600), pause(8, 72), pause(11, 72)]);
+
+        frame.clear();
+        assert_eq!(
+            (frame.bytes(), frame.plan()),
+            ([].as_slice(), [].as_slice())
+        );
+        frame.push(Command::Write(0x4142)).unwrap();
+        assert_eq!(frame.bytes()[0], sync(1));
+    }
+
+    #[test]
+    fn full_frames_are_left_untouched() {
+        let (mut buf, mut plan) = ([0; 7], [EMPTY; 2]);
+        let mut frame = Encoder::new(&mut buf, &mut plan, Oscillator::NOMINAL);
+        frame.push(Command::Home).unwrap();
+        assert_eq!(frame.push(Command::Write(0x4142)), Err(Full));
+        frame.push(Command::Clear).unwrap();
+        assert_eq!(frame.push(Command::Clear), Err(Full));
+        assert_eq!(frame.bytes(), [0xF8, 0x00, 0x20, 0x00, 0x10]);
+        assert_eq!(frame.plan().len(), 2);
+    }
+}
@@ -0,0 +1,322 @@
+//! Read-back verification of RAM writes
+//!
+//! [`Verified`] wraps an interface that can read the RAM and, after each
+//! word written, selects its address again, reads it back and compares it.
+//! Words that don't match are written again, up to the configured number
+//! of retries.
+//!
+//! The address is tracked from the address commands, so writes are only
+//! verified after the address was set through the wrapper.
+//! Since the first read after setting the address returns stale data,
+//! a dummy read is done before reading the word back, and the address of
+//! the next word is selected again after the check.
+
+use crate::{ext, Command, Execute, ExecuteRead};
+
+/// Address of a RAM word
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Address {
+    DdRam(u8),
+    CgRam(u8),
+    GraphicRam { x: u8, y: u8 },
+}
+
+impl Address {
+    /// The address the Address Counter moves to after a write
+    ///
+    /// The entry mode only applies to the DDRAM and CGRAM,
+    /// the GDRAM address always moves to the next word.
+    fn step(self, increment: bool) -> Self {
+        let step = |a: u8| match increment {
+            true => a.wrapping_add(1),
+            false => a.wrapping_sub(1),
+        };
+        match self {
+            Self::DdRam(addr) => Self::DdRam(step(addr) & 0b111111),
+            Self::CgRam(addr) => Self::CgRam(step(addr) & 0b111111),
+            Self::GraphicRam { x, y } => Self::GraphicRam {
+                x: x.wrapping_add(1) & 0b1111,
+                y,
+            },
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Error<E> {
+    /// Error of the wrapped interface
+    Interface(E),
+    /// The word read back was still different after every retry
+    Mismatch {
+        address: Address,
+        written: u16,
+        read: u16,
+    },
+}
+
+pub struct Verified<T> {
+    lcd: T,
+    retries: u8,
+    /// Address the next write goes to, if known
+    address: Option<Address>,
+    /// Whether the Address Counter is incremented after each write
+    increment: bool,
+}
+
+impl<T> Verified<T> {
+    /// Wrap the interface, writing each word up to `1 + retries` times
+    pub fn new(lcd: T, retries: u8) -> Self {
+        Self {
+            lcd,
+            retries,
+            address: None,
+            increment: true,
+        }
+    }
+
+    /// Address the next word is written to, if known
+    pub fn address(&self) -> Option<Address> {
+        self.address
+    }
+
+    pub fn release(self) -> T {
+        self.lcd
+    }
+}
+
+impl<T, E> Verified<T>
+where
+    T: ext::Execute<Error = E> + ExecuteRead<Error = E>,
+{
+    fn select(&mut self, address: Address) -> Result<(), E> {
+        match address {
+            Address::DdRam(addr) => self.lcd.execute(Command::DdRamAddr(addr)),
+            Address::CgRam(addr) => self.lcd.execute(Command::CgRamAddr(addr)),
+            Address::GraphicRam { x, y } => {
+                (self.lcd).execute_ext(ext::Command::GraphicRamAddr { y, x })
+            }
+        }
+    }
+
+    /// Write the word and read it back, returning the word read
+    fn write_read(&mut self, address: Address, data: u16) -> Result<u16, E> {
+        self.lcd.write(data)?;
+        self.select(address)?;
+        self.lcd.read()?;
+        self.lcd.read()
+    }
+
+    fn write_verified(&mut self, address: Address, data: u16) -> Result<(), Error<E>> {
+        let mut read = self.write_read(address, data).map_err(Error::Interface)?;
+        for _ in 0..self.retries {
+            if read == data {
+                break;
+            }
+            log::warn!("RAM W/R! 0x{read:04x} != 0x{data:04x} @ {address:?}, retrying");
+            self.select(address).map_err(Error::Interface)?;
+            read = self.write_read(address, data).map_err(Error::Interface)?;
+        }
+
+        let next = address.step(self.increment);
+        self.select(next).map_err(Error::Interface)?;
+        self.address = Some(next);
+        match read == data {
+            true => Ok(()),
+            false => Err(Error::Mismatch {
+                address,
+                written: data,
+                read,
+            }),
+        }
+    }
+}
+
+// - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - -
+
+impl<T, E> Execute for Verified<T>
+where
+    T: ext::Execute<Error = E> + ExecuteRead<Error = E>,
+{
+    type Error = Error<E>;
+
+    fn execute(&mut self, command: Command) -> Result<(), Self::Error> {
+        if let (Command::Write(data), Some(address)) = (command, self.address) {
+            return self.write_verified(address, data);
+        }
+
+        self.lcd.execute(command).map_err(Error::Interface)?;
+        match command {
+            Command::Clear => {
+                self.address = Some(Address::DdRam(0));
+                self.increment = true;
+            }
+            Command::Home => self.address = Some(Address::DdRam(0)),
+            Command::EntryMode { increment, .. } => self.increment = increment,
+            Command::DdRamAddr(addr) => self.address = Some(Address::DdRam(addr & 0b111111)),
+            Command::CgRamAddr(addr) => self.address = Some(Address::CgRam(addr & 0b111111)),
+            Command::SelectBasic => {
+                if let Some(Address::GraphicRam { .. }) = self.address {
+                    self.address = None;
+                }
+            }
+            _ => (),
+        }
+        Ok(())
+    }
+}
+
+impl<T, E> ext::Execute for Verified<T>
+where
+    T: ext::Execute<Error = E> + ExecuteRead<Error = E>,
+{
+    fn execute_ext(&mut self, command: ext::Command) -> Result<(), Self::Error> {
+        self.lcd.execute_ext(command).map_err(Error::Interface)?;
+        match command {
+            ext::Command::GraphicRamAddr { y, x } => {
+                let (x, y) = (x & 0b1111, y & 0b111111);
+                self.address = Some(Address::GraphicRam { x, y })
+            }
+            // Writes to the IRAM can't be read back
+            ext::Command::IconRamAddr(_) => self.address = None,
+            ext::Command::SelectExtended | ext::Command::SelectGraphic => {
+                if let Some(Address::DdRam(_) | Address::CgRam(_)) = self.address {
+                    self.address = None;
+                }
+            }
+            _ => (),
+        }
+        Ok(())
+    }
+}
+
+/// Reading the RAM moves the Address Counter, so the address
+/// must be set again before the next write is verified
+impl<T, E> ExecuteRead for Verified<T>
+where
+    T: ext::Execute<Error = E> + ExecuteRead<Error = E>,
+{
+    type Error = Error<E>;
+
+    fn read_bf_ac(&mut self) -> Result<(bool, u8), Self::Error> {
+        self.lcd.read_bf_ac().map_err(Error::Interface)
+    }
+
+    fn read(&mut self) -> Result<u16, Self::Error> {
+        self.address = None;
+        self.lcd.read().map_err(Error::Interface)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use core::convert::Infallible;
+
+    use crate::ext::Execute as _;
+    use crate::mock::Emulator;
+
+    use super::*;
+
+    /// Flips the lowest bit of the first `corrupt` words written
+    struct Corrupting {
+        lcd: Emulator,
+        corrupt: usize,
+        writes: usize,
+    }
+
+    impl Corrupting {
+        fn new(corrupt: usize) -> Self {
+            Self {
+                lcd: Emulator::new(),
+                corrupt,
+                writes: 0,
+            }
+        }
+    }
+
+    impl Execute for Corrupting {
+        type Error = Infallible;
+
+        fn execute(&mut self, command: Command) -> Result<(), Self::Error> {
+            match command {
+                Command::Write(data) if self.writes < self.corrupt => {
+                    self.writes += 1;
+                    self.lcd.write(data ^ 1)
+                }
+                Command::Write(data) => {
+                    self.writes += 1;
+                    self.lcd.write(data)
+                }
+                _ => self.lcd.execute(command),
+            }
+        }
+    }
+
+    impl ext::Execute for Corrupting {
+        fn execute_ext(&mut self, command: ext::Command) -> Result<(), Self::Error> {
+            self.lcd.execute_ext(command)
+        }
+    }
+
+    impl ExecuteRead for Corrupting {
+        type Error = Infallible;
+
+        fn read(&mut self) -> Result<u16, Self::Error> {
+            self.lcd.read()
+        }
+
+        fn read_bf_ac(&mut self) -> Result<(bool, u8), Self::Error> {
+            self.lcd.read_bf_ac()
+        }
+    }
+
+    #[test]
+    fn addresses_follow_the_emulator() {
+        use crate::mock::emulator::Address as Ac;
+
+        let mut lcd = Verified::new(Corrupting::new(0), 0);
+        lcd.ddram_addr(0x3E).unwrap();
+        lcd.write_burst(&[0x4142, 0x4344, 0x4546]).unwrap();
+        assert_eq!(lcd.address(), Some(Address::DdRam(0x01)));
+
+        lcd.entry_mode(false, false).unwrap();
+        lcd.select_extended().unwrap();
+        lcd.graphic_ram_addr(15, 5).unwrap();
+        lcd.write_burst(&[0x1234, 0x5678]).unwrap();
+        assert_eq!(lcd.address(), Some(Address::GraphicRam { x: 1, y: 5 }));
+
+        let emulator = lcd.release().lcd;
+        assert_eq!(emulator.address, Ac::GraphicRam { x: 1, y: 5 });
+        assert_eq!(emulator.ddram[0x3E..], [0x4142, 0x4344]);
+        assert_eq!(emulator.ddram[0], 0x4546);
+        assert_eq!(
+            [emulator.gdram[5][15], emulator.gdram[5][0]],
+            [0x1234, 0x5678]
+        );
+    }
+
+    #[test]
+    fn corrupted_words_are_written_again() {
+        let mut lcd = Verified::new(Corrupting::new(2), 2);
+        lcd.ddram_addr(0x10).unwrap();
+        lcd.write_burst(&[0x4142, 0x4344]).unwrap();
+
+        let corrupting = lcd.release();
+        assert_eq!(corrupting.writes, 4);
+        assert_eq!(corrupting.lcd.ddram[0x10..0x12], [0x4142, 0x4344]);
+    }
+
+    #[test]
+    fn words_still_corrupted_after_the_retries_are_reported() {
+        let mut lcd = Verified::new(Corrupting::new(3), 2);
+        lcd.ddram_addr(0x10).unwrap();
+        let mismatch = Error::Mismatch {
+            address: Address::DdRam(0x10),
+            written: 0x4142,
+            read: 0x4143,
+        };
+        assert_eq!(lcd.write(0x4142), Err(mismatch));
+        // The next word goes to the following address anyway
+        assert_eq!(lcd.address(), Some(Address::DdRam(0x11)));
+        assert_eq!(lcd.release().writes, 3);
+    }
+}
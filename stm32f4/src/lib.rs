@@ -8,6 +8,7 @@ use panic_probe as _;
 mod flex;
 mod now;
 pub mod parallel;
+pub mod port;
 pub mod serial;
 
 use stm32f4xx_hal::gpio;
@@ -1,5 +1,5 @@
-use super::{flex::Flex, Input, Output};
-use st7920::parallel::interface::*;
+use super::{flex::Flex, port::Port, Input, Output};
+use st7920::parallel::{interface::*, Timing};
 
 pub fn new_4bit<const NUM: usize>(
     rs: impl Into<Output>,
@@ -20,6 +20,7 @@ pub fn new_4bit<const NUM: usize>(
             Flex::new(db6),
             Flex::new(db7),
         ],
+        Timing::V3_3,
     )
 }
 
@@ -50,5 +51,40 @@ pub fn new_8bit<const NUM: usize>(
             Flex::new(db6),
             Flex::new(db7),
         ],
+        Timing::V3_3,
+    )
+}
+
+/// Like [`new_4bit()`] but writing and reading the data bus all at once,
+/// DB4-7 must be consecutive pins of the same port
+pub fn new_4bit_port<const NUM: usize>(
+    rs: impl Into<Output>,
+    rw: impl Into<Output>,
+    e: [impl Into<Output>; NUM],
+    db: [impl Into<Input>; 4],
+) -> Interface<Output, Port<4>, NUM, 4> {
+    Interface::new(
+        rs.into(),
+        rw.into(),
+        e.map(Into::into),
+        Port::new(db),
+        Timing::V3_3,
+    )
+}
+
+/// Like [`new_8bit()`] but writing and reading the data bus all at once,
+/// DB0-7 must be consecutive pins of the same port
+pub fn new_8bit_port<const NUM: usize>(
+    rs: impl Into<Output>,
+    rw: impl Into<Output>,
+    e: [impl Into<Output>; NUM],
+    db: [impl Into<Input>; 8],
+) -> Interface<Output, Port<8>, NUM, 8> {
+    Interface::new(
+        rs.into(),
+        rw.into(),
+        e.map(Into::into),
+        Port::new(db),
+        Timing::V3_3,
     )
 }
@@ -0,0 +1,96 @@
+use super::Input;
+use core::convert::Infallible;
+use st7920::hal::{OutPort, ParallelPort};
+use stm32f4xx_hal::{gpio::PinExt, pac};
+
+/// Offset between the registers of two consecutive GPIO ports
+///
+/// The STM32F401 ports are mapped on AHB1 from 0x4002_0000 (RM0368,
+/// section 2.3), all with the layout of GPIOA: GPIOA to GPIOE one after
+/// the other and GPIOH at 0x4002_1C00, where GPIOF and GPIOG would follow.
+const PORT_OFFSET: usize = 0x400;
+
+/// Consecutive pins of the same GPIO port, written with a single BSRR write
+/// and read with a single IDR read
+pub struct Port<const N: usize> {
+    _pins: [Input; N],
+    regs: *const pac::gpioa::RegisterBlock,
+    first: u8,
+}
+
+impl<const N: usize> Port<N> {
+    pub fn new(pins: [impl Into<Input>; N]) -> Self {
+        let pins = pins.map(Into::into);
+        let (port, first) = (pins[0].port_id(), pins[0].pin_id());
+        let consecutive =
+            (pins.iter().zip(first..)).all(|(pin, id)| pin.port_id() == port && pin.pin_id() == id);
+        assert!(
+            consecutive,
+            "data bus pins must be consecutive pins of one port"
+        );
+
+        // The port id is the index of the port from GPIOA, so this is the
+        // address of the register block of the port of the pins
+        let regs = (pac::GPIOA::ptr() as usize + PORT_OFFSET * port as usize) as *const _;
+        Self {
+            _pins: pins,
+            regs,
+            first,
+        }
+    }
+
+    fn regs(&self) -> &pac::gpioa::RegisterBlock {
+        // SAFETY: regs points to the register block of an existing port
+        // (the one of the pins), which is valid for the whole program
+        unsafe { &*self.regs }
+    }
+
+    fn mask(&self) -> u32 {
+        (1 << N) - 1
+    }
+
+    /// Set the MODER bits of all the pins in one write
+    fn set_mode(&mut self, mode: u32) {
+        let (mask, bits) = (0..N).fold((0, 0), |(mask, bits), i| {
+            let shift = 2 * (self.first as usize + i);
+            (mask | 0b11 << shift, bits | mode << shift)
+        });
+        // SAFETY: only the two bits of each pin owned by the port are
+        // changed, to a valid mode. The read-modify-write isn't atomic, so
+        // the other pins of the port must not change mode from an interrupt.
+        (self.regs().moder()).modify(|r, w| unsafe { w.bits(r.bits() & !mask | bits) });
+    }
+}
+
+impl<const N: usize> OutPort for Port<N> {
+    type Error = Infallible;
+
+    const WIDTH: usize = N;
+
+    fn set_as_output(&mut self) -> Result<(), Self::Error> {
+        self.set_mode(0b01);
+        Ok(())
+    }
+
+    fn write(&mut self, data: u8) -> Result<(), Self::Error> {
+        let set = data as u32 & self.mask();
+        let reset = !set & self.mask();
+        let bits = set << self.first | reset << (self.first + 16);
+        // SAFETY: any value is valid for BSRR, which atomically sets and
+        // resets only the given bits, all of pins owned by the port
+        self.regs().bsrr().write(|w| unsafe { w.bits(bits) });
+        Ok(())
+    }
+}
+
+impl<const N: usize> ParallelPort for Port<N> {
+    fn set_as_input(&mut self) -> Result<(), Self::Error> {
+        self.set_mode(0b00);
+        Ok(())
+    }
+
+    fn read(&mut self) -> Result<u8, Self::Error> {
+        let idr = self.regs().idr().read().bits();
+        Ok((idr >> self.first & self.mask()) as u8)
+    }
+}
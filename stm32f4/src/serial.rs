@@ -1,22 +1,41 @@
 use super::Output;
-use embedded_hal::spi::SpiBus;
+use core::cell::RefCell;
+use embedded_hal::{delay::DelayNs, spi::SpiBus};
+use embedded_hal_bus::spi::RefCellDevice;
 use fugit::RateExtU32;
+use st7920::serial::device::{ActiveHigh, Device};
 use st7920::serial::Interface;
 use stm32f4xx_hal::{gpio, rcc, spi::*};
 
-pub fn new<SPI: Instance<Miso: From<gpio::NoPin>>, const NUM: usize>(
+pub fn bus<SPI: Instance<Miso: From<gpio::NoPin>>>(
     spi: SPI,
     mosi: impl Into<SPI::Mosi>,
     sck: impl Into<SPI::Sck>,
-    cs: [impl Into<Output>; NUM],
     clocks: &rcc::Clocks,
-) -> Interface<impl SpiBus, Output, NUM> {
+) -> impl SpiBus {
     let mode = Mode {
         polarity: Polarity::IdleHigh,
         phase: Phase::CaptureOnSecondTransition,
     };
-    Interface::new(
-        Spi::new(spi, (sck, NoMiso::new(), mosi), mode, 1.MHz(), clocks),
-        cs.map(Into::into),
-    )
+    Spi::new(spi, (sck, NoMiso::new(), mosi), mode, 1.MHz(), clocks)
+}
+
+pub fn new<SPI: Instance<Miso: From<gpio::NoPin>>, const NUM: usize>(
+    spi: SPI,
+    mosi: impl Into<SPI::Mosi>,
+    sck: impl Into<SPI::Sck>,
+    cs: [impl Into<Output>; NUM],
+    clocks: &rcc::Clocks,
+) -> Interface<impl SpiBus, Output, NUM> {
+    Interface::new(bus(spi, mosi, sck, clocks), cs.map(Into::into))
+}
+
+/// Create an interface on a bus shared with other peripherals
+pub fn new_shared<B: SpiBus, D: DelayNs>(
+    bus: &RefCell<B>,
+    cs: impl Into<Output>,
+    delay: D,
+) -> Device<RefCellDevice<'_, B, ActiveHigh<Output>, D>> {
+    let Ok(device) = RefCellDevice::new(bus, ActiveHigh(cs.into()), delay);
+    Device::new(device)
 }
@@ -0,0 +1,123 @@
+//! Bus level decoders
+//!
+//! Turn a [`Waveform`] into the sequence of bytes that went through the bus,
+//! with the register selected for each of them.
+
+use crate::wave::Waveform;
+
+/// A byte transferred on the bus
+#[derive(Clone, Copy, Debug)]
+pub struct Transfer {
+    /// When the transfer started, in nanoseconds
+    pub start: u64,
+    /// When the transfer ended, in nanoseconds
+    pub end: u64,
+    pub rs: bool,
+    pub rw: bool,
+    pub byte: u8,
+}
+
+/// Decode a parallel bus capture
+///
+/// The data bus is sampled on the falling edge of `E`, using the signals
+/// `RS`, `RW` and `DB0`..`DB7` (or only `DB4`..`DB7` in 4 bit mode, where
+/// every two latches make up a byte, high nibble first).
+pub fn parallel(wave: &Waveform, bits: usize) -> Result<Vec<Transfer>, String> {
+    let rs = wave.require("RS")?;
+    let rw = wave.require("RW")?;
+    let e = wave.require("E")?;
+    let db = (8 - bits..8)
+        .map(|b| wave.require(&format!("DB{b}")))
+        .collect::<Result<Vec<_>, _>>()?;
+
+    let mut transfers = vec![];
+    // Start time and high nibble of a byte in 4 bit mode
+    let mut high: Option<(u64, u8)> = None;
+    let mut start = 0;
+    for step in wave.steps() {
+        if step.rising(e) {
+            start = step.time;
+        }
+        if !step.falling(e) {
+            continue;
+        }
+        let levels = &step.before;
+        let value = db
+            .iter()
+            .rev()
+            .fold(0, |out, &pin| out << 1 | levels[pin] as u8);
+
+        let (start, byte) = match (bits, high.take()) {
+            (8, _) => (start, value),
+            (_, None) => {
+                high = Some((start, value));
+                continue;
+            }
+            (_, Some((start, h))) => (start, h << 4 | value),
+        };
+        transfers.push(Transfer {
+            start,
+            end: step.time,
+            rs: levels[rs],
+            rw: levels[rw],
+            byte,
+        });
+    }
+    Ok(transfers)
+}
+
+/// Decode a serial bus capture
+///
+/// `SID` is sampled on the rising edge of `SCLK` while `CS` is high.
+/// Each transmission starts with a synchronization byte (`11111`, `RW`,
+/// `RS`, `0`) followed by every byte split into two, high nibble first,
+/// each one in the upper half of a byte.
+pub fn serial(wave: &Waveform) -> Result<Vec<Transfer>, String> {
+    let sclk = wave.require("SCLK")?;
+    let sid = wave.require("SID")?;
+    let cs = wave.require("CS")?;
+
+    let mut transfers = vec![];
+    let mut shift = 0u8;
+    let mut count = 0;
+    let mut start = 0;
+    // Register selected by the last synchronization byte
+    let mut select = None;
+    // Start time and high nibble of the current byte
+    let mut high: Option<(u64, u8)> = None;
+    for step in wave.steps() {
+        if step.rising(cs) || step.falling(cs) {
+            (count, select, high) = (0, None, None);
+        }
+        if !step.before[cs] || !step.rising(sclk) {
+            continue;
+        }
+        if count == 0 {
+            start = step.time;
+        }
+        shift = shift << 1 | step.before[sid] as u8;
+        count += 1;
+        if count < 8 {
+            continue;
+        }
+        count = 0;
+
+        if shift & 0b11111000 == 0b11111000 {
+            select = Some((shift & 0b10 != 0, shift & 0b100 != 0));
+            high = None;
+            continue;
+        }
+        let Some((rs, rw)) = select else { continue };
+        match high.take() {
+            None => high = Some((start, shift >> 4)),
+            Some((start, h)) => transfers.push(Transfer {
+                start,
+                end: step.time,
+                rs,
+                rw,
+                byte: h << 4 | shift >> 4,
+            }),
+        }
+    }
+    Ok(transfers)
+}
@@ -0,0 +1,367 @@
+//! Instruction listing
+//!
+//! Decoded bytes are grouped into the driver's own [`Command`]s and
+//! [`ext::Command`]s, following the instruction set selected on the bus,
+//! and checked against their [`execution_time()`](Command::execution_time).
+
+use std::fmt;
+
+use st7920::{ext, Command};
+
+use crate::decode::Transfer;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Kind {
+    Command(Command),
+    Extended(ext::Command),
+    /// A byte that is not a valid instruction
+    Invalid(u8),
+    /// A RAM write, only the first byte is set when the second one is missing
+    Write(u16, bool),
+    /// A RAM read, only the first byte is set when the second one is missing
+    Read(u16, bool),
+    /// A busy flag and address counter read
+    Status {
+        busy: bool,
+        ac: u8,
+    },
+}
+
+impl Kind {
+    /// How long the controller stays busy after this instruction
+    fn execution_time(self) -> st7920::hal::Duration {
+        match self {
+            Self::Command(command) => command.execution_time(),
+            Self::Extended(command) => command.execution_time(),
+            // Reading the RAM takes as long as writing it
+            Self::Write(data, _) | Self::Read(data, _) => Command::Write(data).execution_time(),
+            Self::Invalid(_) | Self::Status { .. } => st7920::hal::Duration::from_ticks(0),
+        }
+    }
+}
+
+impl fmt::Display for Kind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match *self {
+            Self::Command(command) => write!(f, "CMD  {command:?}"),
+            Self::Extended(command) => write!(f, "EXT  {command:?}"),
+            Self::Invalid(byte) => write!(f, "???  0x{byte:02x}"),
+            Self::Write(data, true) => write!(f, "WR   0x{data:04x}"),
+            Self::Write(data, false) => write!(f, "WR   0x{:02x} (incomplete)", data >> 8),
+            Self::Read(data, true) => write!(f, "RD   0x{data:04x}"),
+            Self::Read(data, false) => write!(f, "RD   0x{:02x} (incomplete)", data >> 8),
+            Self::Status { busy, ac } => write!(f, "BF   busy: {busy}, AC: 0x{ac:02x}"),
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug)]
+pub struct Instruction {
+    pub start: u64,
+    pub end: u64,
+    pub kind: Kind,
+}
+
+/// Group the transferred bytes into instructions
+pub fn assemble(transfers: &[Transfer]) -> Vec<Instruction> {
+    let mut out = vec![];
+    let mut extended = false;
+    // First byte of a two bytes instruction or RAM access
+    let mut pending: Option<Transfer> = None;
+
+    for &t in transfers {
+        let instruction = |kind| Instruction {
+            start: pending.map_or(t.start, |p| p.start),
+            end: t.end,
+            kind,
+        };
+        let kind = match (pending, t.rs, t.rw) {
+            (_, false, true) => Kind::Status {
+                busy: t.byte & 0x80 != 0,
+                ac: t.byte & 0x7F,
+            },
+            (Some(p), true, rw) if p.rs && p.rw == rw => {
+                let data = (p.byte as u16) << 8 | t.byte as u16;
+                let kind = match rw {
+                    false => Kind::Write(data, true),
+                    true => Kind::Read(data, true),
+                };
+                out.push(instruction(kind));
+                pending = None;
+                continue;
+            }
+            (Some(p), false, false) if !p.rs => {
+                let command = ext::Command::try_from([p.byte, t.byte]);
+                out.push(instruction(
+                    command.map_or(Kind::Invalid(p.byte), Kind::Extended),
+                ));
+                pending = None;
+                continue;
+            }
+            (_, true, _) => {
+                flush(&mut out, pending.replace(t));
+                continue;
+            }
+            (_, false, false) if extended && t.byte & 0x80 != 0 => {
+                flush(&mut out, pending.replace(t));
+                continue;
+            }
+            (_, false, false) => {
+                let command = match extended {
+                    true => ext::Command::try_from([t.byte, 0])
+                        .map(Kind::Extended)
+                        .or_else(|b| Command::try_from(b).map(Kind::Command)),
+                    false => Command::try_from(t.byte)
+                        .map(Kind::Command)
+                        .or_else(|b| ext::Command::try_from([b, 0]).map(Kind::Extended)),
+                };
+                command.unwrap_or(Kind::Invalid(t.byte))
+            }
+        };
+        match kind {
+            Kind::Command(Command::SelectBasic) => extended = false,
+            Kind::Extended(ext::Command::SelectExtended | ext::Command::SelectGraphic) => {
+                extended = true
+            }
+            _ => (),
+        }
+        flush(&mut out, pending.take());
+        out.push(Instruction {
+            start: t.start,
+            end: t.end,
+            kind,
+        });
+    }
+    flush(&mut out, pending);
+    out
+}
+
+/// Push an instruction that is missing its second byte
+fn flush(out: &mut Vec<Instruction>, pending: Option<Transfer>) {
+    let Some(p) = pending else { return };
+    let data = (p.byte as u16) << 8;
+    let kind = match (p.rs, p.rw) {
+        (true, false) => Kind::Write(data, false),
+        (true, true) => Kind::Read(data, false),
+        _ => Kind::Invalid(p.byte),
+    };
+    out.push(Instruction {
+        start: p.start,
+        end: p.end,
+        kind,
+    });
+}
+
+/// An instruction started before the previous one was done executing
+pub struct Violation {
+    /// The instruction that was still executing
+    pub previous: Kind,
+    /// Time between the end of the previous instruction and the start of this one
+    pub gap: u64,
+    /// The minimum time that should have passed
+    pub required: u64,
+}
+
+/// Check each instruction against the execution time of the previous one
+///
+/// Busy flag reads are never reported, as they are the way
+/// to check whether the controller is still busy.
+pub fn check(instructions: &[Instruction]) -> Vec<Option<Violation>> {
+    let mut busy: Option<(u64, Kind)> = None;
+    instructions
+        .iter()
+        .map(|i| {
+            if let Kind::Status { .. } = i.kind {
+                return None;
+            }
+            let violation = busy.and_then(|(end, previous)| {
+                let gap = i.start.saturating_sub(end);
+                let required = previous.execution_time().to_nanos();
+                (gap < required).then_some(Violation {
+                    previous,
+                    gap,
+                    required,
+                })
+            });
+            busy = Some((i.end, i.kind));
+            violation
+        })
+        .collect()
+}
+
+/// Format nanoseconds as microseconds
+pub struct Micros(pub u64);
+
+impl fmt::Display for Micros {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let width = f.width().unwrap_or(0).saturating_sub(4);
+        write!(f, "{:width$}.{:03}", self.0 / 1000, self.0 % 1000)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use fugit::ExtU32;
+    use st7920::hal::clock;
+    use st7920::mock::{Pin, Recorder};
+    use st7920::parallel::{Interface4Bit, Interface8Bit, Timing};
+    use st7920::serial;
+
+    use super::*;
+    use crate::{decode, wave::vcd};
+
+    /// Every instruction the driver can send, in an order that keeps
+    /// the instruction set selected on the bus consistent
+    fn run<E: ext::Execute>(lcd: &mut E) -> Result<(), E::Error> {
+        lcd.clear()?;
+        lcd.home()?;
+        lcd.entry_mode(true, false)?;
+        lcd.display_on_off(true, false, true)?;
+        lcd.cursor_display_ctrl(true, false)?;
+        lcd.cgram_addr(0x2a)?;
+        lcd.ddram_addr(0x55)?;
+        lcd.write(0x1234)?;
+        lcd.select_extended()?;
+        lcd.stand_by()?;
+        lcd.enable_scroll()?;
+        lcd.scroll_offset(40)?;
+        lcd.enable_cgram()?;
+        lcd.reverse(3)?;
+        lcd.select_graphic()?;
+        lcd.graphic_ram_addr(9, 40)?;
+        lcd.write(0xbeef)?;
+        lcd.select_basic()
+    }
+
+    fn expected() -> Vec<Kind> {
+        use ext::Command as Ext;
+        let basic = [
+            Command::Clear,
+            Command::Home,
+            Command::EntryMode {
+                increment: true,
+                shift: false,
+            },
+            Command::DisplayOnOff {
+                display: true,
+                cursor: false,
+                blink: true,
+            },
+            Command::CursorDisplayCtrl {
+                sc: true,
+                rl: false,
+            },
+            Command::CgRamAddr(0x2a),
+            Command::DdRamAddr(0x55),
+        ];
+        let extended = [
+            Ext::SelectExtended,
+            Ext::StandBy,
+            Ext::EnableScroll,
+            Ext::ScrollOffset(40),
+            Ext::EnableCgRam,
+            Ext::Reverse(3),
+            Ext::SelectGraphic,
+            Ext::GraphicRamAddr { y: 40, x: 9 },
+        ];
+        let basic = basic.map(Kind::Command).into_iter();
+        let extended = extended.map(Kind::Extended).into_iter();
+        (basic.chain([Kind::Write(0x1234, true)]).chain(extended))
+            .chain([
+                Kind::Write(0xbeef, true),
+                Kind::Command(Command::SelectBasic),
+            ])
+            .collect()
+    }
+
+    /// Decode the recording and check it against the expected instructions
+    fn check_listing(
+        recorder: &Recorder,
+        transfers: impl Fn(&crate::wave::Waveform) -> Vec<decode::Transfer>,
+    ) {
+        let wave = vcd::parse(&recorder.vcd()).unwrap();
+        let instructions = assemble(&transfers(&wave));
+        let kinds: Vec<_> = instructions.iter().map(|i| i.kind).collect();
+        assert_eq!(kinds, expected());
+        assert!(check(&instructions).iter().all(Option::is_none));
+    }
+
+    /// RS, RW, E and the upper `N` lines of the data bus
+    fn parallel_pins<const N: usize>(recorder: &Recorder) -> ([Pin; 3], [Pin; N]) {
+        let control = ["RS", "RW", "E"].map(|name| recorder.pin(name));
+        let bus = core::array::from_fn(|i| recorder.pin(&format!("DB{}", 8 - N + i)));
+        (control, bus)
+    }
+
+    #[test]
+    fn parallel_4bit_round_trip() {
+        clock::reset();
+        let recorder = Recorder::new();
+        let ([rs, rw, e], bus) = parallel_pins(&recorder);
+        let mut lcd = Interface4Bit::new(rs, rw, [e], bus, Timing::V5);
+        run(&mut lcd).unwrap();
+        check_listing(&recorder, |wave| decode::parallel(wave, 4).unwrap());
+    }
+
+    #[test]
+    fn parallel_8bit_round_trip() {
+        clock::reset();
+        let recorder = Recorder::new();
+        let ([rs, rw, e], bus) = parallel_pins(&recorder);
+        let mut lcd = Interface8Bit::new(rs, rw, [e], bus, Timing::V5);
+        run(&mut lcd).unwrap();
+        check_listing(&recorder, |wave| decode::parallel(wave, 8).unwrap());
+    }
+
+    #[test]
+    fn serial_round_trip() {
+        clock::reset();
+        let recorder = Recorder::new();
+        let spi = recorder.spi("SCLK", "SID", st7920::hal::Duration::from_ticks(2));
+        let cs = recorder.pin("CS");
+        let mut lcd = serial::Interface::new(spi, [cs]);
+        run(&mut lcd).unwrap();
+        check_listing(&recorder, |wave| decode::serial(wave).unwrap());
+    }
+
+    #[test]
+    fn bitbang_round_trip() {
+        clock::reset();
+        let recorder = Recorder::new();
+        let [sclk, sid, cs] = ["SCLK", "SID", "CS"].map(|name| recorder.pin(name));
+        let spi = serial::bitbang::BitBang::new(sclk, sid, 4000.nanos());
+        let mut lcd = serial::Interface::new(spi, [cs]);
+        run(&mut lcd).unwrap();
+        check_listing(&recorder, |wave| decode::serial(wave).unwrap());
+    }
+
+    #[test]
+    fn serial_frame_round_trip() {
+        use serial::frame::{Encoder, Segment};
+
+        clock::reset();
+        let empty = Segment {
+            end: 0,
+            pause: st7920::hal::Duration::from_ticks(0),
+        };
+        let (mut buf, mut plan) = ([0; 128], [empty; 32]);
+        let mut frame = Encoder::new(&mut buf, &mut plan, Default::default());
+        for kind in expected() {
+            match kind {
+                Kind::Command(command) => frame.push(command),
+                Kind::Extended(command) => frame.push_ext(command),
+                Kind::Write(data, _) => frame.push(Command::Write(data)),
+                _ => unreachable!(),
+            }
+            .unwrap();
+        }
+
+        let recorder = Recorder::new();
+        let spi = recorder.spi("SCLK", "SID", st7920::hal::Duration::from_ticks(2));
+        let cs = recorder.pin("CS");
+        serial::Interface::new(spi, [cs])
+            .send_frame(&frame)
+            .unwrap();
+        check_listing(&recorder, |wave| decode::serial(wave).unwrap());
+    }
+}
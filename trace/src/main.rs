@@ -0,0 +1,93 @@
+//! Decode captured ST7920 bus waveforms into an annotated instruction listing
+//!
+//! ```text
+//! trace <4bit|8bit|serial> <capture.vcd|capture.csv> [NAME=SIGNAL ...]
+//! ```
+//!
+//! The capture format is chosen from the file extension: `.vcd` for
+//! Value Change Dumps and `.csv` for the sigrok CSV output.
+//!
+//! The decoders look for the signals `RS`, `RW`, `E` and `DB0`..`DB7`
+//! on parallel captures, and `SCLK`, `SID` and `CS` on serial ones.
+//! Captures using other names can be mapped with `NAME=SIGNAL` arguments,
+//! like `E=D2`.
+//!
+//! Every instruction that starts before the previous one is done executing
+//! is flagged, and the exit code is non-zero if there are any.
+
+use std::{fs, process::ExitCode};
+
+use listing::Micros;
+
+mod decode;
+mod listing;
+mod wave;
+
+fn run(args: &[String]) -> Result<bool, String> {
+    let [bus, path, renames @ ..] = args else {
+        return Err(
+            "usage: trace <4bit|8bit|serial> <capture.vcd|capture.csv> [NAME=SIGNAL ...]".into(),
+        );
+    };
+
+    let text = fs::read_to_string(path).map_err(|e| format!("{path}: {e}"))?;
+    let mut wave = match path.rsplit_once('.').map(|(_, ext)| ext) {
+        Some("vcd") => wave::vcd::parse(&text)?,
+        Some("csv") => wave::csv::parse(&text)?,
+        _ => return Err(format!("{path}: unknown capture format")),
+    };
+    for rename in renames {
+        let (to, from) = rename
+            .split_once('=')
+            .ok_or_else(|| format!("bad mapping {rename}, expected NAME=SIGNAL"))?;
+        wave.rename(from, to)?;
+    }
+
+    let transfers = match bus.as_str() {
+        "4bit" => decode::parallel(&wave, 4)?,
+        "8bit" => decode::parallel(&wave, 8)?,
+        "serial" => decode::serial(&wave)?,
+        _ => return Err(format!("unknown bus {bus}")),
+    };
+    let instructions = listing::assemble(&transfers);
+    let violations = listing::check(&instructions);
+
+    let mut ok = true;
+    println!("{:>14} {:>12}  instruction", "time [us]", "delta [us]");
+    let mut last = None;
+    for (i, violation) in instructions.iter().zip(&violations) {
+        let delta = last.map_or(0, |last| i.start - last);
+        last = Some(i.start);
+        println!("{:>14} {:>12}  {}", Micros(i.start), Micros(delta), i.kind);
+
+        if let Some(v) = violation {
+            ok = false;
+            println!(
+                "{:>27}  !! started {} us after {}, which needs {} us",
+                "",
+                Micros(v.gap),
+                v.previous,
+                Micros(v.required),
+            );
+        }
+    }
+
+    let count = violations.iter().flatten().count();
+    println!(
+        "{} instructions, {count} timing violations",
+        instructions.len()
+    );
+    Ok(ok)
+}
+
+fn main() -> ExitCode {
+    let args: Vec<_> = std::env::args().skip(1).collect();
+    match run(&args) {
+        Ok(true) => ExitCode::SUCCESS,
+        Ok(false) => ExitCode::FAILURE,
+        Err(e) => {
+            eprintln!("error: {e}");
+            ExitCode::from(2)
+        }
+    }
+}
@@ -0,0 +1,113 @@
+//! Captured logic waveforms
+//!
+//! Both capture formats are turned into a [`Waveform`], a list of
+//! level changes ordered in time, which is what the decoders work on.
+
+pub mod csv;
+pub mod vcd;
+
+/// A level change of a single signal
+#[derive(Clone, Copy, Debug)]
+pub struct Change {
+    /// Time of the change in nanoseconds
+    pub time: u64,
+    /// Index of the signal in [`Waveform::names`]
+    pub signal: usize,
+    pub level: bool,
+}
+
+#[derive(Debug, Default)]
+pub struct Waveform {
+    /// Name of each captured signal
+    pub names: Vec<String>,
+    /// Level of each signal at the start of the capture
+    pub initial: Vec<bool>,
+    /// Level changes sorted by time
+    pub changes: Vec<Change>,
+}
+
+impl Waveform {
+    /// Add a new signal, returning its index
+    pub fn add(&mut self, name: impl Into<String>) -> usize {
+        self.names.push(name.into());
+        self.initial.push(false);
+        self.names.len() - 1
+    }
+
+    /// Find a signal by name, ignoring the case
+    pub fn find(&self, name: &str) -> Option<usize> {
+        self.names.iter().position(|n| n.eq_ignore_ascii_case(name))
+    }
+
+    /// Like [`find()`](Self::find) but fails with a readable message
+    pub fn require(&self, name: &str) -> Result<usize, String> {
+        self.find(name).ok_or_else(|| {
+            let names = self.names.join(", ");
+            format!("signal {name} not found in the capture (available: {names})")
+        })
+    }
+
+    /// Rename a signal, so that captures with different channel
+    /// names can be mapped to the ones the decoders expect
+    pub fn rename(&mut self, from: &str, to: &str) -> Result<(), String> {
+        let signal = self.require(from)?;
+        self.names[signal] = to.into();
+        Ok(())
+    }
+
+    /// Iterate over the changes grouped by time
+    ///
+    /// For each point in time the levels before and after the changes
+    /// are given, so edges can be sampled against the state that was
+    /// stable right before them.
+    pub fn steps(&self) -> Steps<'_> {
+        Steps {
+            changes: &self.changes,
+            levels: self.initial.clone(),
+        }
+    }
+}
+
+pub struct Steps<'a> {
+    changes: &'a [Change],
+    levels: Vec<bool>,
+}
+
+/// The state of the signals around a point in time
+pub struct Step {
+    pub time: u64,
+    pub before: Vec<bool>,
+    pub after: Vec<bool>,
+}
+
+impl Step {
+    pub fn rising(&self, signal: usize) -> bool {
+        !self.before[signal] && self.after[signal]
+    }
+
+    pub fn falling(&self, signal: usize) -> bool {
+        self.before[signal] && !self.after[signal]
+    }
+}
+
+impl Iterator for Steps<'_> {
+    type Item = Step;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let time = self.changes.first()?.time;
+        let count = self.changes.partition_point(|c| c.time == time);
+        let (now, rest) = self.changes.split_at(count);
+        self.changes = rest;
+
+        let before = self.levels.clone();
+        for change in now {
+            self.levels[change.signal] = change.level;
+        }
+        let after = self.levels.clone();
+        Some(Step {
+            time,
+            before,
+            after,
+        })
+    }
+}
@@ -0,0 +1,146 @@
+//! sigrok CSV parser
+//!
+//! Parses the output of `sigrok-cli -O csv`: comment lines start with `;`
+//! (the `Samplerate:` one is used to time the samples), then a header with
+//! the channel names followed by one line per sample.
+//! If the first column is named `Time` it's used as the sample time, in seconds.
+
+use super::{Change, Waveform};
+
+/// Parse a sample rate like `24 MHz`, in samples per second
+fn samplerate(text: &str) -> Result<f64, String> {
+    let text = text.trim();
+    let unit = text.trim_start_matches(|c: char| c.is_ascii_digit() || c == '.');
+    let rate: f64 = text[..text.len() - unit.len()]
+        .parse()
+        .map_err(|_| format!("bad samplerate {text}"))?;
+    let scale = match unit.trim() {
+        "Hz" => 1.0,
+        "kHz" => 1e3,
+        "MHz" => 1e6,
+        "GHz" => 1e9,
+        unit => return Err(format!("bad samplerate unit {unit}")),
+    };
+    Ok(rate * scale)
+}
+
+pub fn parse(text: &str) -> Result<Waveform, String> {
+    let mut wave = Waveform::default();
+    let mut rate = None;
+    let mut timed = false;
+    let mut previous: Option<Vec<bool>> = None;
+
+    let mut sample = 0;
+    for line in text.lines().map(str::trim).filter(|l| !l.is_empty()) {
+        if let Some(comment) = line.strip_prefix(';') {
+            if let Some(value) = comment.trim().strip_prefix("Samplerate:") {
+                rate = Some(samplerate(value)?);
+            }
+            continue;
+        }
+
+        let mut columns = line.split(',').map(str::trim);
+        if wave.names.is_empty() {
+            let first = columns.next().ok_or("empty header")?;
+            timed = first.starts_with("Time");
+            if !timed {
+                wave.add(first);
+            }
+            for name in columns {
+                wave.add(name);
+            }
+            continue;
+        }
+
+        let seconds = match timed {
+            true => {
+                let time = columns.next().ok_or("missing sample time")?;
+                time.parse()
+                    .map_err(|_| format!("bad sample time {time}"))?
+            }
+            false => sample as f64 / rate.ok_or("samplerate not found and no time column")?,
+        };
+        let time = (seconds * 1e9).round() as u64;
+        sample += 1;
+
+        let levels: Vec<bool> = columns.map(|v| v == "1").collect();
+        if levels.len() != wave.names.len() {
+            return Err(format!("sample {sample} has {} channels", levels.len()));
+        }
+        match &previous {
+            None => wave.initial.clone_from(&levels),
+            Some(previous) => {
+                let changed = levels.iter().zip(previous).map(|(a, b)| a != b);
+                let changes = changed.enumerate().filter(|(_, c)| *c);
+                wave.changes.extend(changes.map(|(signal, _)| Change {
+                    time,
+                    signal,
+                    level: levels[signal],
+                }));
+            }
+        }
+        previous = Some(levels);
+    }
+
+    Ok(wave)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const CAPTURE: &str = "
+        ; CSV, generated by libsigrok4DSL
+        ; Samplerate: 2.5 MHz
+        E,RS
+        1,0
+        1,1
+        0,1
+    ";
+
+    fn changes(wave: &Waveform) -> Vec<(u64, usize, bool)> {
+        wave.changes
+            .iter()
+            .map(|c| (c.time, c.signal, c.level))
+            .collect()
+    }
+
+    #[test]
+    fn samplerate_units() {
+        assert_eq!(samplerate(" 24 MHz").unwrap(), 24e6);
+        assert_eq!(samplerate("2.5kHz").unwrap(), 2.5e3);
+        assert_eq!(samplerate("1 GHz").unwrap(), 1e9);
+        assert!(samplerate("MHz").is_err());
+        assert!(samplerate("24 mHz").is_err());
+    }
+
+    #[test]
+    fn samples_are_timed_by_the_samplerate() {
+        let wave = parse(CAPTURE).unwrap();
+        assert_eq!(wave.names, ["E", "RS"]);
+        assert_eq!(wave.initial, [true, false]);
+        assert_eq!(changes(&wave), [(400, 1, true), (800, 0, false)]);
+
+        let error = parse(&CAPTURE.replace("; Samplerate: 2.5 MHz", "")).unwrap_err();
+        assert_eq!(error, "samplerate not found and no time column");
+    }
+
+    #[test]
+    fn time_column_is_used_over_the_samplerate() {
+        let capture = "
+            ; Samplerate: 1 MHz
+            Time [s],E,RS
+            0.0,1,0
+            0.000003,0,0
+        ";
+        let wave = parse(capture).unwrap();
+        assert_eq!(wave.names, ["E", "RS"]);
+        assert_eq!(changes(&wave), [(3000, 0, false)]);
+    }
+
+    #[test]
+    fn samples_must_have_every_channel() {
+        let error = parse(&CAPTURE.replace("1,1", "1,1,0")).unwrap_err();
+        assert_eq!(error, "sample 2 has 3 channels");
+    }
+}
@@ -0,0 +1,168 @@
+//! Value Change Dump parser
+//!
+//! Only the subset needed for logic captures is supported: `wire`
+//! variables (vectors are split into one signal per bit, named
+//! `<name><bit>`), the `$timescale`, `#time` stamps and `0`/`1`/`x`/`z`
+//! values (`x` and `z` are read as low).
+
+use std::{collections::HashMap, str::SplitWhitespace};
+
+use super::{Change, Waveform};
+
+/// Picoseconds per timescale unit
+fn timescale(text: &str) -> Result<u128, String> {
+    let unit = text.trim_start_matches(|c: char| c.is_ascii_digit());
+    let count = &text[..text.len() - unit.len()];
+    let count: u128 = count.parse().map_err(|_| format!("bad timescale {text}"))?;
+    let ps = match unit {
+        "s" => 1_000_000_000_000,
+        "ms" => 1_000_000_000,
+        "us" => 1_000_000,
+        "ns" => 1_000,
+        "ps" => 1,
+        _ => return Err(format!("bad timescale unit {unit}")),
+    };
+    Ok(count * ps)
+}
+
+/// Collect the tokens up to the closing `$end`
+fn section<'a>(tokens: &mut SplitWhitespace<'a>) -> Vec<&'a str> {
+    tokens.take_while(|t| *t != "$end").collect()
+}
+
+pub fn parse(text: &str) -> Result<Waveform, String> {
+    let mut wave = Waveform::default();
+    // Identifier code -> index of the signal of each bit (LSB first)
+    let mut ids: HashMap<&str, Vec<usize>> = HashMap::new();
+    let mut scale = 1_000;
+    let mut time = 0;
+    let mut dumping = true;
+    // Whether the values are the initial ones of `$dumpvars`
+    let mut initial = false;
+
+    let mut tokens = text.split_whitespace();
+    while let Some(token) = tokens.next() {
+        match token {
+            "$timescale" => scale = timescale(&section(&mut tokens).join(""))?,
+            "$var" => match section(&mut tokens)[..] {
+                [_kind, width, id, name, ..] => {
+                    let width: usize = width.parse().map_err(|_| format!("bad width {width}"))?;
+                    // Drop a trailing bit range, like `DB[7:0]`
+                    let name = name.split('[').next().unwrap_or(name);
+                    let bits = match width {
+                        1 => vec![wave.add(name)],
+                        _ => (0..width).map(|b| wave.add(format!("{name}{b}"))).collect(),
+                    };
+                    ids.insert(id, bits);
+                }
+                _ => return Err("malformed $var".into()),
+            },
+            "$dumpoff" => dumping = false,
+            "$dumpon" => dumping = true,
+            "$dumpvars" => initial = true,
+            "$end" => initial = false,
+            "$dumpall" => {}
+            t if t.starts_with('$') => drop(section(&mut tokens)),
+            t if t.starts_with('#') => {
+                let raw: u64 = t[1..].parse().map_err(|_| format!("bad time {t}"))?;
+                time = (raw as u128 * scale / 1_000) as u64;
+            }
+            t if t.starts_with(['b', 'B']) => {
+                let id = tokens.next().ok_or("vector change without identifier")?;
+                let Some(bits) = ids.get(id) else { continue };
+                // Missing high bits are zero-extended
+                let value = t[1..].chars().rev().chain(std::iter::repeat('0'));
+                for (&signal, v) in bits.iter().zip(value) {
+                    change(&mut wave, dumping, initial, time, signal, v == '1');
+                }
+            }
+            t => {
+                let mut chars = t.chars();
+                let value = chars.next();
+                let Some(bits) = ids.get(chars.as_str()) else {
+                    continue;
+                };
+                change(
+                    &mut wave,
+                    dumping,
+                    initial,
+                    time,
+                    bits[0],
+                    value == Some('1'),
+                );
+            }
+        }
+    }
+
+    Ok(wave)
+}
+
+fn change(
+    wave: &mut Waveform,
+    dumping: bool,
+    initial: bool,
+    time: u64,
+    signal: usize,
+    level: bool,
+) {
+    if !dumping {
+        return;
+    }
+    if initial {
+        wave.initial[signal] = level;
+        return;
+    }
+    wave.changes.push(Change {
+        time,
+        signal,
+        level,
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const DUMP: &str = "
+        $timescale 10ns $end
+        $scope module top $end
+        $var wire 1 ! E $end
+        $var wire 2 \" DB[1:0] $end
+        $var wire 1 é RS $end
+        $upscope $end
+        $enddefinitions $end
+        #0
+        $dumpvars 1! b10 \" 1é $end
+        #3 0! b1 \"
+        #5 0é
+    ";
+
+    #[test]
+    fn initial_values_and_changes() {
+        let wave = parse(DUMP).unwrap();
+        assert_eq!(wave.names, ["E", "DB0", "DB1", "RS"]);
+        assert_eq!(wave.initial, [true, false, true, true]);
+
+        let changes: Vec<_> = wave
+            .changes
+            .iter()
+            .map(|c| (c.time, c.signal, c.level))
+            .collect();
+        let expected = [
+            (30, 0, false),
+            (30, 1, true),
+            (30, 2, false),
+            (50, 3, false),
+        ];
+        assert_eq!(changes, expected);
+    }
+
+    #[test]
+    fn non_ascii_value_is_low() {
+        // Unknown identifiers are ignored, other values are read as low
+        let wave = parse(&DUMP.replace("#5 0é", "#5 éx é!")).unwrap();
+        let last = wave.changes.last().unwrap();
+        assert_eq!(wave.changes.len(), 4);
+        assert_eq!((last.time, last.signal, last.level), (50, 0, false));
+    }
+}